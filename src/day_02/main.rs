@@ -2,32 +2,80 @@ use std::io::{self, BufRead};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The motion model can be chosen with an optional argument: `one`, `two` or `3d`.
+/// Without an argument both puzzle parts are run, e.g.
+///   cargo run --bin day_02 < input.txt 3d
 fn main() -> Result<()> {
     let input = read_input()?;
     println!("Input = {:?}", &input);
 
-    part_one(&input);
-    part_two(&input);
+    match std::env::args().nth(1).as_deref() {
+        None => {
+            part_one(&input)?;
+            part_two(&input)?;
+        }
+        Some("one") => part_one(&input)?,
+        Some("two") => part_two(&input)?,
+        Some("3d") => part_three_d(&input)?,
+        Some(model) => Err(format!("unknown motion model {:?}", model))?,
+    }
     Ok(())
 }
 
-fn part_one(input: &[Instruction]) {
+/// describes which instruction caused an arithmetic overflow, counting from 1
+fn overflow_error(idx: usize, inst: &Instruction) -> String {
+    format!(
+        "arithmetic overflow at instruction {} ({:?})",
+        idx + 1,
+        inst
+    )
+}
+
+fn turn_error(idx: usize) -> String {
+    format!(
+        "instruction {} turns, which needs the 3d motion model",
+        idx + 1
+    )
+}
+
+fn product(x: i64, y: i64) -> std::result::Result<i64, String> {
+    x.checked_mul(y)
+        .ok_or_else(|| format!("overflow computing product {} * {}", x, y))
+}
+
+/// follows the course, returning the final (x, depth); a negative depth means the
+/// course ended above the surface
+fn follow_part_one(input: &[Instruction]) -> std::result::Result<(i64, i64), String> {
+    input
+        .iter()
+        .enumerate()
+        .try_fold((0i64, 0i64), |(x, y), (idx, inst)| {
+            match inst {
+                Instruction::Forward(n) => x.checked_add(*n).map(|x| (x, y)),
+                Instruction::Up(n) => y.checked_sub(*n).map(|y| (x, y)),
+                Instruction::Down(n) => y.checked_add(*n).map(|y| (x, y)),
+                Instruction::TurnLeft(_) | Instruction::TurnRight(_) => {
+                    return Err(turn_error(idx));
+                }
+            }
+            .ok_or_else(|| overflow_error(idx, inst))
+        })
+}
+
+fn part_one(input: &[Instruction]) -> Result<()> {
     println!("- part one");
-    let pos = input.iter().fold((0, 0), |(x, y), inst| match inst {
-        Instruction::Forward(n) => (x + n, y),
-        Instruction::Up(n) => (x, y - n),
-        Instruction::Down(n) => (x, y + n),
-    });
+    let pos = follow_part_one(input)?;
     println!("position = {:?}", pos);
     let (x, y) = pos;
-    println!("product = {:?}", x * y);
+    println!("product = {:?}", product(x, y)?);
+    Ok(())
 }
 
 #[derive(Debug)]
 struct State {
-    aim: i32,
-    x: i32,
-    y: i32,
+    aim: i64,
+    x: i64,
+    y: i64,
 }
 
 impl State {
@@ -35,35 +83,125 @@ impl State {
         State { aim: 0, x: 0, y: 0 }
     }
 
-    fn increase_aim(mut self, amount: i32) -> Self {
-        self.aim += amount;
-        self
+    fn increase_aim(mut self, amount: i64) -> Option<Self> {
+        self.aim = self.aim.checked_add(amount)?;
+        Some(self)
     }
 
-    fn move_forward(mut self, amount: i32) -> Self {
-        self.x += amount;
-        self.y += self.aim * amount;
-        self
+    fn move_forward(mut self, amount: i64) -> Option<Self> {
+        self.x = self.x.checked_add(amount)?;
+        self.y = self.y.checked_add(self.aim.checked_mul(amount)?)?;
+        Some(self)
     }
 }
 
-fn part_two(input: &[Instruction]) {
+fn follow_part_two(input: &[Instruction]) -> std::result::Result<State, String> {
+    input
+        .iter()
+        .enumerate()
+        .try_fold(State::new(), |state, (idx, inst)| {
+            match inst {
+                Instruction::Forward(n) => state.move_forward(*n),
+                Instruction::Up(n) => state.increase_aim(-n),
+                Instruction::Down(n) => state.increase_aim(*n),
+                Instruction::TurnLeft(_) | Instruction::TurnRight(_) => {
+                    return Err(turn_error(idx));
+                }
+            }
+            .ok_or_else(|| overflow_error(idx, inst))
+        })
+}
+
+fn part_two(input: &[Instruction]) -> Result<()> {
     println!("- part two");
-    let pos = input.iter().fold(State::new(), |state, inst| match inst {
-        Instruction::Forward(n) => state.move_forward(*n as i32),
-        Instruction::Up(n) => state.increase_aim(-(*n as i32)),
-        Instruction::Down(n) => state.increase_aim(*n as i32),
-    });
+    let pos = follow_part_two(input)?;
     println!("position = {:?}", pos);
     let State { x, y, .. } = pos;
-    println!("product = {:?}", x * y);
+    println!("product = {:?}", product(x, y)?);
+    Ok(())
+}
+
+/// Position and orientation for the 3d model. Heading is measured in degrees
+/// anticlockwise from the x axis when seen from above, and pitch in degrees below
+/// the horizontal, so `down` tips the nose towards the sea floor.
+#[derive(Debug, Default)]
+struct Pose {
+    x: f64,
+    y: f64,
+    depth: f64,
+    heading: f64,
+    pitch: f64,
 }
 
+impl Pose {
+    fn turn(mut self, degrees: f64) -> Self {
+        self.heading = (self.heading + degrees).rem_euclid(360.0);
+        self
+    }
+
+    /// pitch is limited to pointing straight up or straight down
+    fn tilt(mut self, degrees: f64) -> Self {
+        self.pitch = (self.pitch + degrees).clamp(-90.0, 90.0);
+        self
+    }
+
+    fn move_forward(mut self, distance: f64) -> Option<Self> {
+        let (heading, pitch) = (self.heading.to_radians(), self.pitch.to_radians());
+        let horizontal = distance * pitch.cos();
+        self.x += horizontal * heading.cos();
+        self.y += horizontal * heading.sin();
+        self.depth += distance * pitch.sin();
+        [self.x, self.y, self.depth]
+            .iter()
+            .all(|v| v.is_finite())
+            .then_some(self)
+    }
+}
+
+fn follow_three_d(input: &[Instruction]) -> std::result::Result<Pose, String> {
+    input
+        .iter()
+        .enumerate()
+        .try_fold(Pose::default(), |pose, (idx, inst)| {
+            match inst {
+                Instruction::Forward(n) => pose.move_forward(*n as f64),
+                Instruction::Up(n) => Some(pose.tilt(-(*n as f64))),
+                Instruction::Down(n) => Some(pose.tilt(*n as f64)),
+                Instruction::TurnLeft(n) => Some(pose.turn(*n as f64)),
+                Instruction::TurnRight(n) => Some(pose.turn(-(*n as f64))),
+            }
+            .ok_or_else(|| overflow_error(idx, inst))
+        })
+}
+
+fn part_three_d(input: &[Instruction]) -> Result<()> {
+    println!("- 3d model");
+    let pose = follow_three_d(input)?;
+    println!("pose = {:?}", pose);
+    let Pose { x, y, depth, .. } = pose;
+    println!(
+        "distance from start = {}",
+        (x * x + y * y + depth * depth).sqrt()
+    );
+    Ok(())
+}
+
+/// each instruction carries a non-negative distance, or for turns an angle in degrees
 #[derive(Debug)]
 enum Instruction {
-    Forward(u32),
-    Down(u32),
-    Up(u32),
+    Forward(i64),
+    Down(i64),
+    Up(i64),
+    TurnLeft(i64),
+    TurnRight(i64),
+}
+
+fn parse_distance(dist: &str) -> Result<i64> {
+    let dist: i64 = dist.parse()?;
+    if dist < 0 {
+        Err(format!("negative distance {}", dist))?
+    }
+    Ok(dist)
 }
 
 fn read_input() -> Result<Vec<Instruction>> {
@@ -75,12 +213,60 @@ fn read_input() -> Result<Vec<Instruction>> {
             .split_once(' ')
             .ok_or(format!("malformed line: {}", line))?
         {
-            ("forward", dist) => Instruction::Forward(dist.parse()?),
-            ("up", dist) => Instruction::Up(dist.parse()?),
-            ("down", dist) => Instruction::Down(dist.parse()?),
+            ("forward", dist) => Instruction::Forward(parse_distance(dist)?),
+            ("up", dist) => Instruction::Up(parse_distance(dist)?),
+            ("down", dist) => Instruction::Down(parse_distance(dist)?),
+            ("turn", turn) => match turn
+                .split_once(' ')
+                .ok_or(format!("malformed turn: {}", line))?
+            {
+                ("left", angle) => Instruction::TurnLeft(parse_distance(angle)?),
+                ("right", angle) => Instruction::TurnRight(parse_distance(angle)?),
+                inst => Err(format!("unexpected turn {:?}", inst))?,
+            },
             inst => Err(format!("unexpected instruction {:?}", inst))?,
         };
         instructions.push(instruction);
     }
     Ok(instructions)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{follow_part_one, follow_part_two, follow_three_d, Instruction as I};
+
+    #[test]
+    fn course_above_surface() {
+        let course = [I::Forward(3), I::Up(5), I::Down(2)];
+        assert_eq!(follow_part_one(&course), Ok((3, -3)));
+        let state = follow_part_two(&[I::Up(2), I::Forward(4)]).unwrap();
+        assert_eq!((state.x, state.y), (4, -8));
+    }
+
+    #[test]
+    fn overflow_names_instruction() {
+        let course = [I::Down(1), I::Forward(i64::MAX), I::Forward(1)];
+        assert_eq!(
+            follow_part_one(&course),
+            Err("arithmetic overflow at instruction 3 (Forward(1))".to_string())
+        );
+        let course = [I::Down(i64::MAX), I::Forward(2)];
+        assert!(follow_part_two(&course)
+            .unwrap_err()
+            .contains("instruction 2"));
+    }
+
+    #[test]
+    fn three_d_turns_and_dives() {
+        let course = [I::Forward(10), I::TurnLeft(90), I::Down(90), I::Forward(4)];
+        let pose = follow_three_d(&course).unwrap();
+        assert!((pose.x - 10.0).abs() < 1e-9);
+        assert!(pose.y.abs() < 1e-9);
+        assert!((pose.depth - 4.0).abs() < 1e-9);
+
+        let course = [I::TurnRight(90), I::Up(30), I::Forward(2)];
+        let pose = follow_three_d(&course).unwrap();
+        assert!((pose.y + 3f64.sqrt()).abs() < 1e-9);
+        assert!((pose.depth + 1.0).abs() < 1e-9);
+    }
+}
@@ -1,78 +1,470 @@
+use bitvec::prelude::*;
+use num_bigint::BigUint;
+use std::fmt::{self, Display};
 use std::io::{self, BufRead};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Optional arguments change how the ratings are chosen: `lsb` considers the least
+/// significant bit first, and `strict` filters on bits that every candidate shares
+/// instead of skipping them, e.g.
+///   cargo run --bin day_03 < input.txt lsb strict
 fn main() -> Result<()> {
-    let input = read_input()?;
-    part_one(&input);
-    part_two(&input);
+    let mut bit_order = BitOrder::MsbFirst;
+    let mut skip_unanimous = true;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "msb" => bit_order = BitOrder::MsbFirst,
+            "lsb" => bit_order = BitOrder::LsbFirst,
+            "strict" => skip_unanimous = false,
+            arg => Err(format!("unexpected argument {:?}", arg))?,
+        }
+    }
+    let report = read_input()?.sort_rows(bit_order);
+    part_one(&report.report);
+    part_two(&report, skip_unanimous)?;
 
     Ok(())
 }
 
-fn part_one(input: &[Vec<bool>]) {
+/// A diagnostic report, packed two ways. Column-wise, so that the most common value
+/// of each bit is a word-level popcount, and row-wise in sorted order, so that the
+/// ratings can be found by partitioning a range of rows one bit at a time.
+#[derive(Clone)]
+struct Report {
+    width: usize,
+    /// `columns[n]` holds bit `n` (counting from the most significant) of every row
+    columns: Vec<BitVec<Lsb0, u64>>,
+    /// each row as big-endian words, right-aligned so that leading bits are padding
+    rows: Vec<u64>,
+    words_per_row: usize,
+}
+
+impl Report {
+    fn new(width: usize) -> Self {
+        Report {
+            width,
+            columns: vec![BitVec::new(); width],
+            rows: vec![],
+            words_per_row: width.div_ceil(64),
+        }
+    }
+
+    fn push(&mut self, bits: &[bool]) {
+        let mut row = vec![0u64; self.words_per_row];
+        let padding = self.words_per_row * 64 - self.width;
+        for (bit_n, &bit) in bits.iter().enumerate() {
+            self.columns[bit_n].push(bit);
+            let offset = padding + bit_n;
+            row[offset / 64] |= (bit as u64) << (63 - offset % 64);
+        }
+        self.rows.extend(row);
+    }
+
+    fn len(&self) -> usize {
+        self.rows.len() / self.words_per_row
+    }
+
+    fn row(&self, idx: usize) -> &[u64] {
+        &self.rows[idx * self.words_per_row..(idx + 1) * self.words_per_row]
+    }
+
+    fn bit(&self, idx: usize, bit_n: usize) -> bool {
+        let offset = self.words_per_row * 64 - self.width + bit_n;
+        (self.row(idx)[offset / 64] >> (63 - offset % 64)) & 1 == 1
+    }
+
+    /// LSD radix sort of the rows, one bit at a time starting from the bit that is
+    /// considered last, so that sorting costs O(n·w) for any bit order
+    fn sort_rows(mut self, bit_order: BitOrder) -> SortedReport {
+        let len = self.len();
+        let mut order = (0..len).collect::<Vec<_>>();
+        let mut scratch = vec![0; len];
+        for &bit_n in bit_order.positions(self.width).iter().rev() {
+            let count_true = self.columns[bit_n].count_ones();
+            let (mut next_false, mut next_true) = (0, len - count_true);
+            for &idx in &order {
+                if self.bit(idx, bit_n) {
+                    scratch[next_true] = idx;
+                    next_true += 1;
+                } else {
+                    scratch[next_false] = idx;
+                    next_false += 1;
+                }
+            }
+            std::mem::swap(&mut order, &mut scratch);
+        }
+        self.rows = order
+            .into_iter()
+            .flat_map(|idx| self.row(idx).to_vec())
+            .collect();
+        SortedReport {
+            report: self,
+            bit_order,
+        }
+    }
+}
+
+/// a report whose rows are sorted in the order their bits are considered, which is
+/// what choosing a rating relies on
+#[derive(Clone)]
+struct SortedReport {
+    report: Report,
+    bit_order: BitOrder,
+}
+
+/// reads right-aligned big-endian words as an unsigned number of any width
+fn words_to_biguint(words: &[u64]) -> BigUint {
+    BigUint::new(
+        words
+            .iter()
+            .rev()
+            .flat_map(|&word| [word as u32, (word >> 32) as u32])
+            .collect(),
+    )
+}
+
+fn part_one(report: &Report) {
     println!("part_one");
-    let width = input[0].len();
-    let most_common = (0..width)
-        .map(|bit_n| input.iter().filter(|num| num[bit_n]).count() > input.len() / 2)
-        .collect::<Vec<_>>();
-    let least_common = most_common.iter().map(|b| !b).collect::<Vec<_>>();
-    println!(
-        "power level = {}",
-        bit_vec_to_u32(&most_common) * bit_vec_to_u32(&least_common)
-    );
+    let (gamma, epsilon) = gamma_epsilon(report);
+    println!("gamma = {}, epsilon = {}", gamma, epsilon);
+    println!("power level = {}", gamma * epsilon);
 }
 
-fn bit_vec_to_u32(vec: &[bool]) -> u32 {
-    assert!(vec.len() <= 32);
-    vec.iter().fold(0, |acc, &bit| acc << 1 | bit as u32)
+/// the gamma rate, made of the most common bits, and the epsilon rate made of the rest
+fn gamma_epsilon(report: &Report) -> (BigUint, BigUint) {
+    let mut gamma = Report::new(report.width);
+    let most_common = report
+        .columns
+        .iter()
+        .map(|column| column.count_ones() > report.len() / 2)
+        .collect::<Vec<_>>();
+    gamma.push(&most_common);
+    let mut epsilon = Report::new(report.width);
+    epsilon.push(&most_common.iter().map(|b| !b).collect::<Vec<_>>());
+    let gamma = words_to_biguint(gamma.row(0));
+    let epsilon = words_to_biguint(epsilon.row(0));
+    (gamma, epsilon)
 }
 
-fn part_two(input: &[Vec<bool>]) {
+fn part_two(report: &SortedReport, skip_unanimous: bool) -> Result<()> {
     println!("part_two");
 
-    let oxygen = choose_for_criteria(input, true);
-    println!("oxygen {:?} / {}", oxygen, bit_vec_to_u32(oxygen));
-    let co2 = choose_for_criteria(input, false);
-    println!("co2 {:?} / {}", co2, bit_vec_to_u32(co2));
-    let life_support_rating = bit_vec_to_u32(oxygen) * bit_vec_to_u32(co2);
+    let oxygen = Criteria {
+        rule: Rule::MostCommon,
+        tie_break: true,
+        skip_unanimous,
+    };
+    let oxygen = words_to_biguint(choose_for_criteria(report, &oxygen)?);
+    println!("oxygen = {}", oxygen);
+    let co2 = Criteria {
+        rule: Rule::LeastCommon,
+        tie_break: false,
+        skip_unanimous,
+    };
+    let co2 = words_to_biguint(choose_for_criteria(report, &co2)?);
+    println!("co2 = {}", co2);
+    let life_support_rating = oxygen * co2;
     println!("life support rating = {}", life_support_rating);
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// bit positions, counting from the most significant, in the order they are used
+    fn positions(self, width: usize) -> Vec<usize> {
+        match self {
+            BitOrder::MsbFirst => (0..width).collect(),
+            BitOrder::LsbFirst => (0..width).rev().collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rule {
+    MostCommon,
+    LeastCommon,
+}
+
+/// how to whittle the report down to a single rating
+#[derive(Clone, Copy, Debug)]
+struct Criteria {
+    rule: Rule,
+    /// the bit value to keep when ones and zeros are equally common
+    tie_break: bool,
+    /// whether a bit that every remaining candidate shares is passed over, rather
+    /// than applying the rule to it (which empties the candidates for `LeastCommon`)
+    skip_unanimous: bool,
 }
 
-fn choose_for_criteria(input: &[Vec<bool>], criteria: bool) -> &Vec<bool> {
-    let width = input[0].len();
-    let mut numbers = input.iter().collect::<Vec<_>>();
-    for bit_n in 0..width {
-        let count_true = numbers.iter().filter(|num| num[bit_n]).count();
-        let bit_value_to_retain = (count_true * 2 >= numbers.len()) ^ criteria;
-        numbers.retain(|num| num[bit_n] == bit_value_to_retain);
-        if numbers.len() == 1 {
+#[derive(Debug, PartialEq, Eq)]
+enum SelectionError {
+    /// filtering on this bit left no candidates
+    Emptied { bit_n: usize },
+    /// every bit has been used, the last being `bit_n`, but more than one candidate
+    /// is left because they are duplicates
+    NotNarrowed { bit_n: usize, remaining: usize },
+}
+
+impl Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionError::Emptied { bit_n } => {
+                write!(f, "no candidates left after filtering on bit {}", bit_n)
+            }
+            SelectionError::NotNarrowed { bit_n, remaining } => write!(
+                f,
+                "{} candidates still left after filtering on bit {}",
+                remaining, bit_n
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+/// Bits are considered in the order the report is sorted by, and the candidates
+/// left after narrowing on the first `n` of those bits share them, so they are a
+/// contiguous range ordered by the next bit; each step is then a binary search for
+/// where that bit changes from 0 to 1. Bit positions in errors count from the most
+/// significant bit.
+fn choose_for_criteria<'a>(
+    sorted: &'a SortedReport,
+    criteria: &Criteria,
+) -> std::result::Result<&'a [u64], SelectionError> {
+    let report = &sorted.report;
+    let (mut lo, mut hi) = (0, report.len());
+    let mut last_bit = 0;
+    for bit_n in sorted.bit_order.positions(report.width) {
+        if hi - lo == 1 {
             break;
         }
-        if numbers.len() == 0 {
-            panic!("Oops, no numbers left");
+        last_bit = bit_n;
+        let (mut first_true, mut end) = (lo, hi);
+        while first_true < end {
+            let mid = first_true + (end - first_true) / 2;
+            if report.bit(mid, bit_n) {
+                end = mid;
+            } else {
+                first_true = mid + 1;
+            }
+        }
+        let count_true = hi - first_true;
+        let count_false = first_true - lo;
+        if criteria.skip_unanimous && (count_true == 0 || count_false == 0) {
+            // every candidate agrees on this bit, e.g. the leading zeros of a wide
+            // report, so it can't narrow the choice down
+            continue;
+        }
+        let bit_value_to_retain = match count_true.cmp(&count_false) {
+            std::cmp::Ordering::Equal => criteria.tie_break,
+            std::cmp::Ordering::Greater => criteria.rule == Rule::MostCommon,
+            std::cmp::Ordering::Less => criteria.rule == Rule::LeastCommon,
+        };
+        if bit_value_to_retain {
+            lo = first_true;
+        } else {
+            hi = first_true;
+        }
+        if lo == hi {
+            return Err(SelectionError::Emptied { bit_n });
         }
     }
-    if numbers.len() > 1 {
-        panic!("Still too many numbers left ({})", numbers.len());
+    if hi - lo > 1 {
+        return Err(SelectionError::NotNarrowed {
+            bit_n: last_bit,
+            remaining: hi - lo,
+        });
     }
-    numbers[0]
+    Ok(report.row(lo))
 }
 
-fn read_input() -> Result<Vec<Vec<bool>>> {
-    let mut nums = vec![];
+/// every line of the report must have the same number of bits, and at least one
+fn read_input() -> Result<Report> {
+    let mut report: Option<Report> = None;
     let stdin = io::stdin();
     for line in stdin.lock().lines() {
         let line = line?;
-        nums.push(
-            line.chars()
-                .map(|ch| match ch {
-                    '1' => Ok(true),
-                    '0' => Ok(false),
-                    c => Err(format!("Unexpected character '{}'", c)),
-                })
-                .collect::<std::result::Result<_, _>>()?,
+        let bits = line
+            .chars()
+            .map(|ch| match ch {
+                '1' => Ok(true),
+                '0' => Ok(false),
+                c => Err(format!("Unexpected character '{}'", c)),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if report.is_none() && bits.is_empty() {
+            Err("the first line of the report has no bits")?
+        }
+        let report = report.get_or_insert_with(|| Report::new(bits.len()));
+        if bits.len() != report.width {
+            Err(format!(
+                "line {} has {} bits, expected {}",
+                report.len() + 1,
+                bits.len(),
+                report.width
+            ))?
+        }
+        report.push(&bits);
+    }
+    Ok(report.ok_or("empty diagnostic report")?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        choose_for_criteria, gamma_epsilon, words_to_biguint, BitOrder, Criteria, Report, Rule,
+        SelectionError, SortedReport,
+    };
+    use num_bigint::BigUint;
+
+    fn build_report(rows: &[u128], width: usize, bit_order: BitOrder) -> SortedReport {
+        let mut report = Report::new(width);
+        for row in rows {
+            let bits = (0..width)
+                .map(|bit_n| (row >> (width - 1 - bit_n)) & 1 == 1)
+                .collect::<Vec<_>>();
+            report.push(&bits);
+        }
+        report.sort_rows(bit_order)
+    }
+
+    fn criteria(rule: Rule, tie_break: bool) -> Criteria {
+        Criteria {
+            rule,
+            tie_break,
+            skip_unanimous: true,
+        }
+    }
+
+    const EXAMPLE: [u128; 12] = [
+        0b00100, 0b11110, 0b10110, 0b10111, 0b10101, 0b01111, 0b00111, 0b11100, 0b10000, 0b11001,
+        0b00010, 0b01010,
+    ];
+
+    #[test]
+    fn wide_values() {
+        let report = build_report(&[u128::MAX, 1 << 99, 5], 128, BitOrder::MsbFirst).report;
+        assert_eq!(report.row(0), &[0, 5]);
+        assert_eq!(words_to_biguint(report.row(1)), BigUint::from(1u128 << 99));
+        assert_eq!(words_to_biguint(report.row(2)), BigUint::from(u128::MAX));
+        let report = build_report(&[1 << 99, 3], 100, BitOrder::MsbFirst).report;
+        assert_eq!(words_to_biguint(report.row(1)), BigUint::from(1u128 << 99));
+    }
+
+    /// gamma, epsilon, oxygen and CO2 ratings, then the power and life support ratings
+    fn ratings(rows: &[u128], width: usize) -> [BigUint; 6] {
+        let report = build_report(rows, width, BitOrder::MsbFirst);
+        let (gamma, epsilon) = gamma_epsilon(&report.report);
+        let oxygen = choose_for_criteria(&report, &criteria(Rule::MostCommon, true)).unwrap();
+        let co2 = choose_for_criteria(&report, &criteria(Rule::LeastCommon, false)).unwrap();
+        let (oxygen, co2) = (words_to_biguint(oxygen), words_to_biguint(co2));
+        let power = &gamma * &epsilon;
+        let life_support = &oxygen * &co2;
+        [gamma, epsilon, oxygen, co2, power, life_support]
+    }
+
+    fn expected(rates: [u128; 4], power: &str, life_support: &str) -> [BigUint; 6] {
+        let [gamma, epsilon, oxygen, co2] = rates.map(BigUint::from);
+        [
+            gamma,
+            epsilon,
+            oxygen,
+            co2,
+            power.parse().unwrap(),
+            life_support.parse().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn wide_ratings() {
+        let rows = [
+            0x677a0668d61a36ff,
+            0xa137fd0fdbb9b4cc,
+            0x46d68fe18b4ad08b,
+            0xe0f579c53f512bea,
+            0x08df974aa290b5a2,
+            0x7187d50af93feff9,
+        ];
+        assert_eq!(
+            ratings(&rows, 64),
+            expected(
+                [
+                    0x60d795489b18b4ea,
+                    0x9f286ab764e74b15,
+                    0x7187d50af93feff9,
+                    0xa137fd0fdbb9b4cc,
+                ],
+                "80029840445904150658791977302545491250",
+                "95035935756117725362494883953243016812",
+            )
+        );
+        let rows = [
+            0x501800011051578f93076741794a5b02,
+            0x13ac7021799bfdc34bcc33f7451b15e1,
+            0x2a2894a3294ac9fde34a6de5e9ca6d5d,
+            0x1a92f40cbe4891ff0d978f4ad45d6c7f,
+            0x4b4fcd098c3f5bd3b7609f2aac7d2eae,
+            0xae2d7a87d39a187788dacb130d0e462b,
+        ];
+        assert_eq!(
+            ratings(&rows, 128),
+            expected(
+                [
+                    0x0a085001181a59d783420f434d4a4c2b,
+                    0xf5f7affee7e5a6287cbdf0bcb2b5b3d4,
+                    0x1a92f40cbe4891ff0d978f4ad45d6c7f,
+                    0xae2d7a87d39a187788dacb130d0e462b,
+                ],
+                "4359981444567760010304286923206131485545484341961913268042431825069325952156",
+                "8178034042041881464299780508156352543495663723543590423945759996511471399765",
+            )
+        );
+    }
+
+    #[test]
+    fn example_ratings() {
+        let report = build_report(&EXAMPLE, 5, BitOrder::MsbFirst);
+        assert_eq!(report.report.columns[0].count_ones(), 7);
+        let pick = |criteria| words_to_biguint(choose_for_criteria(&report, &criteria).unwrap());
+        let oxygen = criteria(Rule::MostCommon, true);
+        assert_eq!(pick(oxygen), BigUint::from(23u8));
+        let co2 = criteria(Rule::LeastCommon, false);
+        assert_eq!(pick(co2), BigUint::from(10u8));
+    }
+
+    #[test]
+    fn least_significant_bit_first() {
+        let report = build_report(&EXAMPLE, 5, BitOrder::LsbFirst);
+        let pick = |criteria| words_to_biguint(choose_for_criteria(&report, &criteria).unwrap());
+        // the last bit splits 5 ones from 7 zeros, and so on towards the first bit
+        let most_common = criteria(Rule::MostCommon, true);
+        assert_eq!(pick(most_common), BigUint::from(0b11110u8));
+        let least_common = criteria(Rule::LeastCommon, false);
+        assert_eq!(pick(least_common), BigUint::from(0b11001u8));
+    }
+
+    #[test]
+    fn selection_errors() {
+        let report = build_report(&[0b100, 0b101, 0b101], 3, BitOrder::MsbFirst);
+        let mut co2 = criteria(Rule::LeastCommon, false);
+        co2.skip_unanimous = false;
+        assert_eq!(
+            choose_for_criteria(&report, &co2),
+            Err(SelectionError::Emptied { bit_n: 0 })
+        );
+        let oxygen = criteria(Rule::MostCommon, true);
+        assert_eq!(
+            choose_for_criteria(&report, &oxygen),
+            Err(SelectionError::NotNarrowed {
+                bit_n: 2,
+                remaining: 2
+            })
         );
     }
-    Ok(nums)
 }
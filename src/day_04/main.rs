@@ -1,14 +1,55 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
-use std::io::{self, BufRead};
+use std::io::{self, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The winning patterns can be given as a comma-separated argument, choosing from
+/// `rows`, `columns`, `diagonals`, `corners`, `x`, `blackout` and `mask:<file>`,
+/// where the file holds masks drawn with `#` and `.`, separated by blank lines.
+/// Without an argument boards win with a full row or column. An `events=<file>`
+/// argument writes the game's events to a file as JSON lines, e.g.
+///   cargo run --bin day_04 < input.txt rows,diagonals,mask:masks.txt events=game.jsonl
+///
+/// Those events can be replayed to show a board as it was after a given turn,
+/// where turn 0 is before any number is drawn, e.g.
+///   cargo run --bin day_04 < input.txt replay game.jsonl <board> <turn>
+///
+/// To estimate each board's chances over shuffled draw orders, give a number of
+/// trials and a seed, optionally followed by the winning patterns. Every order is
+/// played instead when the draw pool is small enough, e.g.
+///   cargo run --bin day_04 < input.txt simulate 10000 42 rows,columns
 fn main() -> Result<()> {
-    let mut bingo = read_input()?;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("replay") => return replay(&args[1..]),
+        Some("simulate") => return simulate(&args[1..]),
+        _ => {}
+    }
+    let mut patterns = vec![Pattern::Rows, Pattern::Columns];
+    let mut events_path = None;
+    for arg in args {
+        match arg.strip_prefix("events=") {
+            Some(path) => events_path = Some(path.to_string()),
+            None => patterns = parse_patterns(&arg)?,
+        }
+    }
+    let mut bingo = read_input(patterns)?;
+    bingo.check_winnable()?;
+
+    let result = play_game(&mut bingo);
+    if let Some(path) = events_path {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for event in &bingo.events {
+            writeln!(file, "{}", event.to_json())?;
+        }
+    }
+    result
+}
 
+fn play_game(bingo: &mut Bingo) -> Result<()> {
     while let Some(drawn_number) = bingo.draw_number() {
-        while let Some(winner) = bingo.play(drawn_number) {
+        for winner in bingo.play(drawn_number) {
             println!(
                 "board {} wins after drawing number {}",
                 winner, drawn_number
@@ -28,6 +69,335 @@ fn main() -> Result<()> {
     Err("Ran out of numbers to draw before everyone had a chance to win")?
 }
 
+/// re-marks a fresh copy of the boards from an event log, up to and including the
+/// given turn
+fn replay(args: &[String]) -> Result<()> {
+    let [path, board_idx, turn] = args else {
+        Err("usage: replay <events file> <board> <turn>")?
+    };
+    let (board_idx, turn): (usize, usize) = (board_idx.parse()?, turn.parse()?);
+    let mut bingo = read_input(vec![])?;
+    if board_idx >= bingo.boards.len() {
+        Err(format!("there is no board {}", board_idx))?
+    }
+    let mut last_drawn = None;
+    for line in std::fs::read_to_string(path)?.lines() {
+        match Event::from_json(line)? {
+            Event::NumberDrawn { turn: t, number } if t <= turn => last_drawn = Some(number),
+            Event::CellMarked {
+                turn: t,
+                board,
+                row,
+                column,
+            } if t <= turn && board == board_idx => {
+                let grid = &bingo.boards[board].grid;
+                if row >= grid.len() || column >= grid[0].len() {
+                    Err(format!(
+                        "turn {} marks row {} column {}, outside board {}, which is {}x{}",
+                        t,
+                        row,
+                        column,
+                        board,
+                        grid.len(),
+                        grid[0].len()
+                    ))?
+                }
+                bingo.boards[board].mark(row, column);
+            }
+            Event::BoardWon {
+                turn: t,
+                board,
+                score,
+            } if t <= turn && board == board_idx => {
+                println!("board {} won on turn {} with score {}", board, t, score);
+            }
+            _ => {}
+        }
+    }
+    match last_drawn {
+        Some(number) => println!(
+            "board {} after turn {}, which drew number {}:",
+            board_idx, turn, number
+        ),
+        None => println!("board {} before any number is drawn:", board_idx),
+    }
+    print!("{:?}", bingo.boards[board_idx]);
+    Ok(())
+}
+
+/// draw pools up to this size are analysed exactly, by playing every order
+const EXACT_POOL_LIMIT: usize = 8;
+
+fn simulate(args: &[String]) -> Result<()> {
+    let (trials, seed, patterns) = match args {
+        [trials, seed] => (trials, seed, vec![Pattern::Rows, Pattern::Columns]),
+        [trials, seed, patterns] => (trials, seed, parse_patterns(patterns)?),
+        _ => Err("usage: simulate <trials> <seed> [patterns]")?,
+    };
+    let (trials, seed): (usize, u64) = (trials.parse()?, seed.parse()?);
+    if trials == 0 {
+        Err("simulate needs at least one trial")?
+    }
+    let bingo = read_input(patterns)?;
+    bingo.check_winnable()?;
+    let pool = bingo.draw_sequence.len();
+    if pool <= EXACT_POOL_LIMIT {
+        println!("playing all orders of {} numbers", pool);
+    } else {
+        println!("playing {} shuffled orders with seed {}", trials, seed);
+    }
+    win_stats(&bingo, trials, seed).print();
+    Ok(())
+}
+
+/// plays every order of the draw pool if it's small enough, or else `trials`
+/// shuffled orders
+fn win_stats(bingo: &Bingo, trials: usize, seed: u64) -> WinStats {
+    let mut pool = bingo.draw_sequence.iter().copied().collect::<Vec<_>>();
+    let mut stats = WinStats::new(bingo.boards.len());
+    if pool.len() <= EXACT_POOL_LIMIT {
+        for_each_permutation(&mut pool, &mut |order| {
+            stats.record(&win_turns(bingo, order))
+        });
+    } else {
+        let mut rng = SplitMix64(seed);
+        for _ in 0..trials {
+            rng.shuffle(&mut pool);
+            stats.record(&win_turns(bingo, &pool));
+        }
+    }
+    stats
+}
+
+/// plays a fresh copy of the game with the given draw order, returning the turn
+/// that each board won on, if it did
+fn win_turns(bingo: &Bingo, order: &[u8]) -> Vec<Option<usize>> {
+    let mut game = bingo.clone();
+    game.draw_sequence = order.iter().copied().collect();
+    let mut turns = vec![None; game.boards.len()];
+    while let Some(drawn_number) = game.draw_number() {
+        for winner in game.play(drawn_number) {
+            turns[winner] = Some(game.turn);
+        }
+        if game.game_complete() {
+            break;
+        }
+    }
+    turns
+}
+
+/// Heap's algorithm, calling `f` with every ordering of `items`
+fn for_each_permutation(items: &mut [u8], f: &mut impl FnMut(&[u8])) {
+    let mut counters = vec![0; items.len()];
+    f(items);
+    let mut idx = 1;
+    while idx < items.len() {
+        if counters[idx] < idx {
+            items.swap(if idx % 2 == 0 { 0 } else { counters[idx] }, idx);
+            f(items);
+            counters[idx] += 1;
+            idx = 1;
+        } else {
+            counters[idx] = 0;
+            idx += 1;
+        }
+    }
+}
+
+/// a small seeded generator, so that simulations can be repeated exactly
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            let other = (self.next() % (idx as u64 + 1)) as usize;
+            items.swap(idx, other);
+        }
+    }
+}
+
+/// Boards that win on the same turn share the credit for winning first or last.
+/// Winning last only counts in games where every board wins.
+struct WinStats {
+    games: usize,
+    first: Vec<f64>,
+    last: Vec<f64>,
+    wins: Vec<usize>,
+    total_turns: Vec<usize>,
+}
+
+impl WinStats {
+    fn new(boards: usize) -> Self {
+        WinStats {
+            games: 0,
+            first: vec![0.0; boards],
+            last: vec![0.0; boards],
+            wins: vec![0; boards],
+            total_turns: vec![0; boards],
+        }
+    }
+
+    fn record(&mut self, turns: &[Option<usize>]) {
+        self.games += 1;
+        for (board, turn) in turns.iter().enumerate() {
+            if let Some(turn) = turn {
+                self.wins[board] += 1;
+                self.total_turns[board] += turn;
+            }
+        }
+        let share = |tally: &mut [f64], turn| {
+            let tied = turns.iter().filter(|&&t| t == Some(turn)).count();
+            for (board, &t) in turns.iter().enumerate() {
+                if t == Some(turn) {
+                    tally[board] += 1.0 / tied as f64;
+                }
+            }
+        };
+        if let Some(first_turn) = turns.iter().flatten().min() {
+            share(&mut self.first, *first_turn);
+        }
+        if turns.iter().all(|turn| turn.is_some()) {
+            if let Some(last_turn) = turns.iter().flatten().max() {
+                share(&mut self.last, *last_turn);
+            }
+        }
+    }
+
+    /// each board's chance of winning first and of winning last, and its expected
+    /// winning turn in the games it wins
+    fn per_board(&self) -> Vec<(f64, f64, Option<f64>)> {
+        (0..self.first.len())
+            .map(|board| {
+                let expected_turn = match self.wins[board] {
+                    0 => None,
+                    wins => Some(self.total_turns[board] as f64 / wins as f64),
+                };
+                (
+                    self.first[board] / self.games as f64,
+                    self.last[board] / self.games as f64,
+                    expected_turn,
+                )
+            })
+            .collect()
+    }
+
+    fn print(&self) {
+        println!("board | P(first) | P(last) | expected winning turn");
+        for (board, (first, last, expected_turn)) in self.per_board().into_iter().enumerate() {
+            let expected_turn = match expected_turn {
+                None => "never wins".to_string(),
+                Some(turn) => format!("{:.2}", turn),
+            };
+            println!(
+                "{:5} | {:8.4} | {:7.4} | {}",
+                board, first, last, expected_turn
+            );
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Event {
+    NumberDrawn {
+        turn: usize,
+        number: u8,
+    },
+    CellMarked {
+        turn: usize,
+        board: usize,
+        row: usize,
+        column: usize,
+    },
+    BoardWon {
+        turn: usize,
+        board: usize,
+        score: u32,
+    },
+    GameComplete {
+        turn: usize,
+    },
+}
+
+impl Event {
+    fn to_json(&self) -> String {
+        match self {
+            Event::NumberDrawn { turn, number } => format!(
+                r#"{{"event":"number_drawn","turn":{},"number":{}}}"#,
+                turn, number
+            ),
+            Event::CellMarked {
+                turn,
+                board,
+                row,
+                column,
+            } => format!(
+                r#"{{"event":"cell_marked","turn":{},"board":{},"row":{},"column":{}}}"#,
+                turn, board, row, column
+            ),
+            Event::BoardWon { turn, board, score } => format!(
+                r#"{{"event":"board_won","turn":{},"board":{},"score":{}}}"#,
+                turn, board, score
+            ),
+            Event::GameComplete { turn } => {
+                format!(r#"{{"event":"game_complete","turn":{}}}"#, turn)
+            }
+        }
+    }
+
+    /// parses the flat objects written by `to_json`
+    fn from_json(line: &str) -> Result<Self> {
+        let fields = line
+            .trim()
+            .strip_prefix('{')
+            .and_then(|line| line.strip_suffix('}'))
+            .ok_or(format!("not a JSON object: {}", line))?
+            .split(',')
+            .map(|field| {
+                let (key, value) = field
+                    .split_once(':')
+                    .ok_or(format!("malformed field {:?}", field))?;
+                Ok((key.trim().trim_matches('"'), value.trim().trim_matches('"')))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let field = |name: &str| -> Result<usize> {
+            Ok(fields
+                .get(name)
+                .ok_or(format!("missing {:?} in {}", name, line))?
+                .parse()?)
+        };
+        let turn = field("turn")?;
+        let event = match fields.get("event").copied() {
+            Some("number_drawn") => Event::NumberDrawn {
+                turn,
+                number: field("number")?.try_into()?,
+            },
+            Some("cell_marked") => Event::CellMarked {
+                turn,
+                board: field("board")?,
+                row: field("row")?,
+                column: field("column")?,
+            },
+            Some("board_won") => Event::BoardWon {
+                turn,
+                board: field("board")?,
+                score: field("score")?.try_into()?,
+            },
+            Some("game_complete") => Event::GameComplete { turn },
+            event => Err(format!("unknown event {:?}", event))?,
+        };
+        Ok(event)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum CellState {
     Marked,
@@ -48,9 +418,178 @@ impl Cell {
 
 type Grid = Vec<Vec<Cell>>;
 
+/// cells, as (row, column), that win when all of them are marked
+type Mask = Vec<(usize, usize)>;
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Rows,
+    Columns,
+    /// either diagonal, on square boards only
+    Diagonals,
+    Corners,
+    /// both diagonals together, on square boards only
+    X,
+    Blackout,
+    /// a user-defined mask, for boards of the same size only
+    Custom(Vec<Vec<bool>>),
+}
+
+impl Pattern {
+    fn masks(&self, rows: usize, columns: usize) -> Vec<Mask> {
+        let square = rows == columns;
+        let diagonal = || (0..rows).map(|idx| (idx, idx));
+        let anti_diagonal = || (0..rows).map(move |idx| (idx, columns - 1 - idx));
+        let mut masks: Vec<Mask> = match self {
+            Pattern::Rows => (0..rows)
+                .map(|row| (0..columns).map(|column| (row, column)).collect())
+                .collect(),
+            Pattern::Columns => (0..columns)
+                .map(|column| (0..rows).map(|row| (row, column)).collect())
+                .collect(),
+            Pattern::Diagonals if square => {
+                vec![diagonal().collect(), anti_diagonal().collect()]
+            }
+            Pattern::X if square => vec![diagonal().chain(anti_diagonal()).collect()],
+            Pattern::Diagonals | Pattern::X => vec![],
+            Pattern::Corners => vec![vec![
+                (0, 0),
+                (0, columns - 1),
+                (rows - 1, 0),
+                (rows - 1, columns - 1),
+            ]],
+            Pattern::Blackout => vec![(0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (row, column)))
+                .collect()],
+            Pattern::Custom(mask) if mask.len() == rows && mask[0].len() == columns => {
+                vec![(0..rows)
+                    .flat_map(|row| (0..columns).map(move |column| (row, column)))
+                    .filter(|&(row, column)| mask[row][column])
+                    .collect()]
+            }
+            Pattern::Custom(_) => vec![],
+        };
+        // the diagonals of an X share the middle cell of an odd-sized board, and
+        // corners coincide on a board one cell high or wide
+        for mask in &mut masks {
+            mask.sort_unstable();
+            mask.dedup();
+        }
+        masks
+    }
+}
+
+fn parse_patterns(arg: &str) -> Result<Vec<Pattern>> {
+    let mut patterns = vec![];
+    for name in arg.split(',') {
+        match name {
+            "rows" => patterns.push(Pattern::Rows),
+            "columns" => patterns.push(Pattern::Columns),
+            "diagonals" => patterns.push(Pattern::Diagonals),
+            "corners" => patterns.push(Pattern::Corners),
+            "x" => patterns.push(Pattern::X),
+            "blackout" => patterns.push(Pattern::Blackout),
+            name => match name.strip_prefix("mask:") {
+                Some(path) => patterns.extend(read_masks(path)?),
+                None => Err(format!("unknown winning pattern {:?}", name))?,
+            },
+        }
+    }
+    Ok(patterns)
+}
+
+/// masks are drawn with `#` for cells that must be marked and `.` for the rest
+fn read_masks(path: &str) -> Result<Vec<Pattern>> {
+    let mut masks = vec![];
+    for block in std::fs::read_to_string(path)?.split("\n\n") {
+        let mask = block
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.trim()
+                    .chars()
+                    .map(|ch| match ch {
+                        '#' => Ok(true),
+                        '.' => Ok(false),
+                        c => Err(format!("unexpected character {:?} in {}", c, path)),
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if mask.is_empty() {
+            continue;
+        }
+        if !mask.iter().flatten().any(|&cell| cell) {
+            Err(format!(
+                "mask {} in {} has no cells to mark",
+                masks.len(),
+                path
+            ))?
+        }
+        if mask.iter().any(|row| row.len() != mask[0].len()) {
+            Err(format!(
+                "mask {} in {} is not rectangular",
+                masks.len(),
+                path
+            ))?
+        }
+        masks.push(Pattern::Custom(mask));
+    }
+    Ok(masks)
+}
+
+#[derive(Clone)]
 struct Board {
     grid: Grid,
     has_won: bool,
+    /// the size of each winning mask, and how many of its cells are marked so far
+    mask_sizes: Vec<usize>,
+    marked_in_mask: Vec<usize>,
+    /// for each cell, the indices of the masks that contain it
+    masks_for_cell: Vec<Vec<Vec<usize>>>,
+}
+
+impl Board {
+    fn new(grid: Grid, patterns: &[Pattern]) -> Self {
+        let (rows, columns) = (grid.len(), grid[0].len());
+        let masks = patterns
+            .iter()
+            .flat_map(|pattern| pattern.masks(rows, columns))
+            .filter(|mask| !mask.is_empty())
+            .collect::<Vec<_>>();
+        let mut masks_for_cell = vec![vec![vec![]; columns]; rows];
+        for (mask_idx, mask) in masks.iter().enumerate() {
+            for &(row, column) in mask {
+                masks_for_cell[row][column].push(mask_idx);
+            }
+        }
+        Board {
+            grid,
+            has_won: false,
+            marked_in_mask: vec![0; masks.len()],
+            mask_sizes: masks.iter().map(|mask| mask.len()).collect(),
+            masks_for_cell,
+        }
+    }
+
+    fn can_win(&self) -> bool {
+        !self.mask_sizes.is_empty()
+    }
+
+    /// marks a cell, returning whether that completed one of the winning masks
+    fn mark(&mut self, row: usize, column: usize) -> bool {
+        let cell = &mut self.grid[row][column];
+        if cell.is_marked() {
+            return false;
+        }
+        cell.state = CellState::Marked;
+        let mut completed = false;
+        for &mask_idx in &self.masks_for_cell[row][column] {
+            self.marked_in_mask[mask_idx] += 1;
+            completed |= self.marked_in_mask[mask_idx] == self.mask_sizes[mask_idx];
+        }
+        completed
+    }
 }
 
 impl Debug for Board {
@@ -70,40 +609,105 @@ impl Debug for Board {
     }
 }
 
-#[derive(Debug)]
+/// where a number appears, as (board, row, column)
+type Location = (usize, usize, usize);
+
+#[derive(Clone, Debug)]
 struct Bingo {
     draw_sequence: VecDeque<u8>,
     boards: Vec<Board>,
+    /// every location of each number, indexed by the number
+    locations: Vec<Vec<Location>>,
+    /// how many numbers have been drawn so far
+    turn: usize,
+    events: Vec<Event>,
 }
 
 impl Bingo {
-    fn is_winning_grid(grid: &Grid) -> bool {
-        grid.iter()
-            .any(|row| row.iter().all(|cell| cell.is_marked()))
-            || (0..5).any(|idx| grid.iter().map(|row| row[idx]).all(|cell| cell.is_marked()))
+    fn new(draw_sequence: VecDeque<u8>, grids: Vec<Grid>, patterns: &[Pattern]) -> Self {
+        let mut locations = vec![vec![]; u8::MAX as usize + 1];
+        for (board_idx, grid) in grids.iter().enumerate() {
+            for (row, cells) in grid.iter().enumerate() {
+                for (column, cell) in cells.iter().enumerate() {
+                    locations[cell.number as usize].push((board_idx, row, column));
+                }
+            }
+        }
+        Bingo {
+            draw_sequence,
+            boards: grids
+                .into_iter()
+                .map(|grid| Board::new(grid, patterns))
+                .collect(),
+            locations,
+            turn: 0,
+            events: vec![],
+        }
+    }
+
+    /// fails if a board has no winning masks, as the game could then never finish
+    fn check_winnable(&self) -> Result<()> {
+        match self.boards.iter().position(|board| !board.can_win()) {
+            Some(board_idx) => {
+                let grid = &self.boards[board_idx].grid;
+                Err(format!(
+                    "board {} is {}x{}, and none of the winning patterns fit it",
+                    board_idx,
+                    grid.len(),
+                    grid[0].len()
+                ))?
+            }
+            None => Ok(()),
+        }
     }
 
     fn draw_number(&mut self) -> Option<u8> {
-        self.draw_sequence.pop_front()
+        let number = self.draw_sequence.pop_front()?;
+        self.turn += 1;
+        self.events.push(Event::NumberDrawn {
+            turn: self.turn,
+            number,
+        });
+        Some(number)
     }
 
     fn game_complete(&self) -> bool {
         self.boards.iter().all(|board| board.has_won)
     }
 
-    fn play(&mut self, drawn_number: u8) -> Option<usize> {
-        for (idx, board) in self.boards.iter_mut().enumerate() {
-            for row in board.grid.iter_mut() {
-                if let Some(cell) = row.iter_mut().find(|cell| cell.number == drawn_number) {
-                    cell.state = CellState::Marked;
-                }
-            }
-            if !board.has_won && Bingo::is_winning_grid(&board.grid) {
+    /// marks the number on the boards that contain it, returning the boards that
+    /// won as a result, in order
+    fn play(&mut self, drawn_number: u8) -> Vec<usize> {
+        let mut winners = vec![];
+        for &(board_idx, row, column) in &self.locations[drawn_number as usize] {
+            let board = &mut self.boards[board_idx];
+            let was_unmarked = !board.grid[row][column].is_marked();
+            if board.mark(row, column) && !board.has_won {
                 board.has_won = true;
-                return Some(idx);
+                winners.push(board_idx);
+            }
+            if was_unmarked {
+                self.events.push(Event::CellMarked {
+                    turn: self.turn,
+                    board: board_idx,
+                    row,
+                    column,
+                });
             }
         }
-        return None;
+        winners.sort_unstable();
+        for &board in &winners {
+            let score = self.score_board(board, drawn_number);
+            self.events.push(Event::BoardWon {
+                turn: self.turn,
+                board,
+                score,
+            });
+        }
+        if !winners.is_empty() && self.game_complete() {
+            self.events.push(Event::GameComplete { turn: self.turn });
+        }
+        winners
     }
 
     fn score_board(&self, board_idx: usize, drawn_number: u8) -> u32 {
@@ -125,43 +729,308 @@ impl Bingo {
     }
 }
 
-fn read_input() -> Result<Bingo> {
-    let stdin = io::stdin();
-    let mut first_line = String::new();
-    stdin.read_line(&mut first_line)?;
-    let draw_sequence: VecDeque<u8> = first_line
+fn read_input(patterns: Vec<Pattern>) -> Result<Bingo> {
+    parse_input(&io::read_to_string(io::stdin())?, patterns)
+}
+
+/// Boards follow the draw sequence, separated by blank lines. Each board can be any
+/// size, as long as all of its rows are the same length.
+fn parse_input(input: &str, patterns: Vec<Pattern>) -> Result<Bingo> {
+    let mut lines = input.lines();
+    let draw_sequence: VecDeque<u8> = lines
+        .next()
+        .ok_or("no draw sequence")?
         .trim()
-        .split(",")
+        .split(',')
         .map(|s| s.parse())
         .collect::<std::result::Result<_, _>>()?;
-    let mut boards = vec![];
-    let mut current_board = vec![];
-    for (idx, line) in stdin.lock().lines().enumerate() {
-        let line = line?;
-        if idx % 6 != 0 {
-            let nums = line
-                .trim()
-                .split_whitespace()
-                .map(|s| {
-                    s.parse().map(|number| Cell {
-                        number,
+    let mut grids = vec![];
+    let mut current_board: Grid = vec![];
+    for line in lines.chain([""]) {
+        if line.trim().is_empty() {
+            if !current_board.is_empty() {
+                grids.push(current_board);
+                current_board = vec![];
+            }
+            continue;
+        }
+        let nums = line
+            .split_whitespace()
+            .map(|s| {
+                s.parse().map(|number| Cell {
+                    number,
+                    state: CellState::Unmarked,
+                })
+            })
+            .collect::<std::result::Result<Vec<Cell>, _>>()?;
+        if let Some(first_row) = current_board.first() {
+            if first_row.len() != nums.len() {
+                Err(format!(
+                    "board {} has rows of length {} and {}",
+                    grids.len(),
+                    first_row.len(),
+                    nums.len()
+                ))?
+            }
+        }
+        current_board.push(nums);
+    }
+    Ok(Bingo::new(draw_sequence, grids, &patterns))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        for_each_permutation, parse_input, win_stats, Bingo, Board, Cell, CellState, Event, Grid,
+        Pattern, SplitMix64,
+    };
+
+    fn grid(rows: usize, columns: usize) -> Grid {
+        (0..rows)
+            .map(|row| {
+                (0..columns)
+                    .map(|column| Cell {
+                        number: (row * columns + column) as u8,
                         state: CellState::Unmarked,
                     })
-                })
-                .collect::<std::result::Result<Vec<Cell>, _>>()?;
-            current_board.push(nums);
-        }
-        if idx % 6 == 5 {
-            assert_eq!(current_board.len(), 5);
-            boards.push(Board {
-                grid: current_board,
-                has_won: false,
-            });
-            current_board = vec![];
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn sizes(input: &str) -> Vec<(usize, usize)> {
+        parse_input(input, vec![Pattern::Rows, Pattern::Columns])
+            .unwrap()
+            .boards
+            .iter()
+            .map(|board| (board.grid.len(), board.grid[0].len()))
+            .collect()
+    }
+
+    #[test]
+    fn boards_separated_by_blank_lines() {
+        let bingo = parse_input(include_str!("test.txt"), vec![Pattern::Rows]).unwrap();
+        assert_eq!(bingo.draw_sequence.len(), 27);
+        assert_eq!(bingo.boards.len(), 3);
+        assert_eq!(bingo.boards[2].grid[4][4].number, 7);
+        // runs of blank lines, and blank lines with spaces, separate boards too
+        assert_eq!(
+            sizes("1,2\n\n\n1 2\n3 4\n  \n5 6\n7 8\n\n"),
+            vec![(2, 2), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn board_sizes_per_board() {
+        assert_eq!(
+            sizes("1,2,3\n\n1 2 3\n\n4\n5\n\n6 7\n8 9\n10 11"),
+            vec![(1, 3), (2, 1), (3, 2)]
+        );
+        // boards of different sizes play in one game
+        let mut bingo = parse_input("5,4,1\n\n1 2 3\n\n4\n5", vec![Pattern::Columns]).unwrap();
+        assert!(bingo.play(5).is_empty());
+        assert_eq!(bingo.play(4), vec![1]);
+        assert_eq!(bingo.play(1), vec![0]);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let error = parse_input("1\n\n1 2\n3 4\n\n5 6\n7", vec![])
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "board 1 has rows of length 2 and 1");
+        assert!(parse_input("", vec![]).is_err());
+        assert!(parse_input("1,x\n\n1", vec![]).is_err());
+    }
+
+    /// plays until the numbers run out, returning each winning board and its score
+    fn winners(bingo: &mut Bingo) -> Vec<(usize, u32)> {
+        let mut winners = vec![];
+        while let Some(drawn_number) = bingo.draw_number() {
+            for winner in bingo.play(drawn_number) {
+                winners.push((winner, bingo.score_board(winner, drawn_number)));
+            }
+        }
+        winners
+    }
+
+    #[test]
+    fn example_game() {
+        let patterns = vec![Pattern::Rows, Pattern::Columns];
+        let mut bingo = parse_input(include_str!("test.txt"), patterns).unwrap();
+        let winners = winners(&mut bingo);
+        assert_eq!(winners.len(), 3);
+        assert_eq!(winners.first(), Some(&(2, 4512)));
+        assert_eq!(winners.last(), Some(&(1, 1924)));
+        assert!(bingo.game_complete());
+    }
+
+    #[test]
+    fn number_twice_on_a_board() {
+        let mut bingo = parse_input("4,1\n\n1 1\n2 3", vec![Pattern::Rows]).unwrap();
+        assert_eq!(winners(&mut bingo), vec![(0, 5)]);
+        assert!(bingo.boards[0].grid[0].iter().all(|cell| cell.is_marked()));
+        assert_eq!(bingo.boards[0].marked_in_mask, vec![2, 0]);
+        let marked = bingo
+            .events
+            .iter()
+            .filter(|event| matches!(event, Event::CellMarked { turn: 2, .. }))
+            .count();
+        assert_eq!(marked, 2);
+    }
+
+    #[test]
+    fn boards_winning_on_the_same_draw() {
+        let input = "1,3,2,9\n\n1 2\n5 6\n\n3 4\n7 8\n\n3 2\n7 8";
+        let mut bingo = parse_input(input, vec![Pattern::Rows]).unwrap();
+        assert_eq!(bingo.locations[2], vec![(0, 0, 1), (2, 0, 1)]);
+        assert!(bingo.play(1).is_empty());
+        assert!(bingo.play(3).is_empty());
+        assert_eq!(bingo.play(2), vec![0, 2]);
+        assert_eq!(bingo.score_board(0, 2), 22);
+        assert_eq!(bingo.score_board(2, 2), 30);
+        assert!(bingo.play(9).is_empty());
+        assert!(!bingo.game_complete());
+        let won = bingo
+            .events
+            .iter()
+            .filter(|event| matches!(event, Event::BoardWon { .. }))
+            .count();
+        assert_eq!(won, 2);
+    }
+
+    #[test]
+    fn every_order_of_a_small_pool() {
+        let mut orders = vec![];
+        for_each_permutation(&mut [1, 2, 3, 4], &mut |order| orders.push(order.to_vec()));
+        assert_eq!(orders.len(), 24);
+        orders.sort();
+        orders.dedup();
+        assert_eq!(orders.len(), 24);
+
+        // boards 0 and 1 tie whenever 1 is drawn first, and board 2 wins alone
+        // when 2 is drawn first
+        let bingo = parse_input("1,2\n\n1\n\n1\n\n2", vec![Pattern::Rows]).unwrap();
+        assert_eq!(
+            win_stats(&bingo, 1, 0).per_board(),
+            vec![
+                (0.25, 0.25, Some(1.5)),
+                (0.25, 0.25, Some(1.5)),
+                (0.5, 0.5, Some(1.5))
+            ]
+        );
+        // winning last only counts when every board wins
+        let bingo = parse_input("1,2\n\n1\n\n3", vec![Pattern::Rows]).unwrap();
+        assert_eq!(
+            win_stats(&bingo, 1, 0).per_board(),
+            vec![(1.0, 0.0, Some(1.5)), (0.0, 0.0, None)]
+        );
+    }
+
+    #[test]
+    fn shuffles_repeat_with_a_seed() {
+        let mut items = (0..20).collect::<Vec<u8>>();
+        SplitMix64(7).shuffle(&mut items);
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        assert_ne!(items, sorted);
+
+        let patterns = vec![Pattern::Rows, Pattern::Columns];
+        let bingo = parse_input(include_str!("test.txt"), patterns).unwrap();
+        let stats = win_stats(&bingo, 50, 42).per_board();
+        assert_eq!(win_stats(&bingo, 50, 42).per_board(), stats);
+        let first = stats.iter().map(|&(first, _, _)| first).sum::<f64>();
+        assert!((first - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pattern_masks() {
+        assert_eq!(
+            Pattern::Diagonals.masks(3, 3),
+            vec![vec![(0, 0), (1, 1), (2, 2)], vec![(0, 2), (1, 1), (2, 0)]]
+        );
+        assert_eq!(
+            Pattern::X.masks(3, 3),
+            vec![vec![(0, 0), (0, 2), (1, 1), (2, 0), (2, 2)]]
+        );
+        assert_eq!(
+            Pattern::X.masks(2, 2),
+            vec![vec![(0, 0), (0, 1), (1, 0), (1, 1)]]
+        );
+        assert_eq!(
+            Pattern::Corners.masks(3, 4),
+            vec![vec![(0, 0), (0, 3), (2, 0), (2, 3)]]
+        );
+        assert_eq!(Pattern::Corners.masks(1, 3), vec![vec![(0, 0), (0, 2)]]);
+        assert_eq!(Pattern::Corners.masks(3, 1), vec![vec![(0, 0), (2, 0)]]);
+        assert_eq!(Pattern::Corners.masks(1, 1), vec![vec![(0, 0)]]);
+        assert_eq!(Pattern::Blackout.masks(1, 2), vec![vec![(0, 0), (0, 1)]]);
+        for (rows, columns) in [(2, 3), (3, 2)] {
+            assert!(Pattern::Diagonals.masks(rows, columns).is_empty());
+            assert!(Pattern::X.masks(rows, columns).is_empty());
+        }
+        let custom = Pattern::Custom(vec![vec![true, false, false], vec![false, false, true]]);
+        assert_eq!(custom.masks(2, 3), vec![vec![(0, 0), (1, 2)]]);
+        assert!(custom.masks(3, 2).is_empty());
+        assert!(custom.masks(2, 2).is_empty());
+    }
+
+    /// marks every cell in order, returning the cells whose marking won
+    fn winning_marks(board: &mut Board) -> Vec<(usize, usize)> {
+        let (rows, columns) = (board.grid.len(), board.grid[0].len());
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .filter(|&(row, column)| board.mark(row, column))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_cells_are_marked_once() {
+        let mut board = Board::new(grid(3, 3), &[Pattern::X]);
+        assert_eq!(winning_marks(&mut board), vec![(2, 2)]);
+        let mut board = Board::new(grid(1, 3), &[Pattern::Corners]);
+        assert!(!board.mark(0, 0));
+        assert!(!board.mark(0, 0));
+        assert!(!board.mark(0, 1));
+        assert!(board.mark(0, 2));
+        let mut board = Board::new(grid(1, 1), &[Pattern::Corners]);
+        assert!(board.mark(0, 0));
+    }
+
+    #[test]
+    fn custom_masks_that_never_win() {
+        let wrong_size = Pattern::Custom(vec![vec![true; 3]; 3]);
+        let no_cells = Pattern::Custom(vec![vec![false; 2]; 2]);
+        for pattern in [wrong_size, no_cells] {
+            let mut board = Board::new(grid(2, 2), &[pattern]);
+            assert!(!board.can_win());
+            assert!(winning_marks(&mut board).is_empty());
+        }
+        let mut board = Board::new(grid(2, 2), &[Pattern::Diagonals, Pattern::Rows]);
+        assert!(board.can_win());
+        assert_eq!(winning_marks(&mut board), vec![(0, 1), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let events = [
+            Event::NumberDrawn { turn: 1, number: 7 },
+            Event::CellMarked {
+                turn: 1,
+                board: 2,
+                row: 3,
+                column: 4,
+            },
+            Event::BoardWon {
+                turn: 12,
+                board: 2,
+                score: 4512,
+            },
+            Event::GameComplete { turn: 15 },
+        ];
+        for event in events {
+            assert_eq!(Event::from_json(&event.to_json()).unwrap(), event);
         }
     }
-    Ok(Bingo {
-        draw_sequence,
-        boards,
-    })
 }
@@ -1,32 +1,168 @@
-use std::collections::HashSet;
-use std::io::{self, BufRead};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Part two takes segments at any angle. By default a segment covers only the points
+/// that lie exactly on it, and overlaps are counted exactly from the segments
+/// themselves. The `bresenham` argument has each segment cover the points drawn by
+/// Bresenham's line algorithm instead. The `raster` argument counts by listing every
+/// point on every segment, which is only practical for short segments, and is always
+/// used for `bresenham`. Coordinates may be negative and as large as
+/// `COORDINATE_LIMIT`, e.g.
+///   cargo run --bin day_05 < input.txt bresenham
+///
+/// Queries on all the segments can follow, always treating segments as their
+/// lattice points, so they can't be combined with `bresenham`: `at-least=<k>` counts
+/// the points covered by at least `k` segments, `at=<x>,<y>` gives the coverage at a
+/// point, `pairs` lists which segments overlap and where, and `max` gives the highest
+/// coverage and how many points have it. A heatmap of coverage per cell, using the
+/// chosen rasterisation, is written by `heatmap=<file>` as a `.pgm` or `.png` image,
+/// or drawn as text by `ascii`, e.g.
+///   cargo run --bin day_05 < input.txt at-least=3 at=10,20 heatmap=vents.png
 fn main() -> Result<()> {
+    let mut mode = Rasterisation::Lattice;
+    let mut raster = false;
+    let mut queries = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "lattice" => mode = Rasterisation::Lattice,
+            "bresenham" => mode = Rasterisation::Bresenham,
+            "raster" => raster = true,
+            arg => queries.push(parse_query(arg)?),
+        }
+    }
+    let by_lattice = queries
+        .iter()
+        .any(|query| !matches!(query, Query::Heatmap(_) | Query::Ascii));
+    if mode == Rasterisation::Bresenham && by_lattice {
+        Err(
+            "queries other than heatmap and ascii treat segments as lattice points, \
+             so they can't be used with bresenham",
+        )?
+    }
     let input = read_input()?;
-    part_1(&input);
-    part_2(&input);
+    part_1(&input, raster);
+    part_2(&input, mode, raster);
+    if !queries.is_empty() {
+        run_queries(&input, mode, &queries)?;
+    }
     Ok(())
 }
 
-fn part_1(input: &[LineSegment]) {
+#[derive(Debug)]
+enum Query {
+    AtLeast(usize),
+    At(Point),
+    Pairs,
+    Max,
+    Heatmap(String),
+    Ascii,
+}
+
+fn parse_query(arg: &str) -> Result<Query> {
+    Ok(match arg.split_once('=') {
+        None => match arg {
+            "pairs" => Query::Pairs,
+            "max" => Query::Max,
+            "ascii" => Query::Ascii,
+            arg => Err(format!("unexpected argument {:?}", arg))?,
+        },
+        Some(("at-least", k)) => match k.parse()? {
+            0 => Err("at-least needs k of at least 1")?,
+            k => Query::AtLeast(k),
+        },
+        Some(("at", point)) => {
+            let (x, y) = point.split_once(',').ok_or("expected at=<x>,<y>")?;
+            Query::At((parse_coordinate(x)?, parse_coordinate(y)?))
+        }
+        Some(("heatmap", path)) => Query::Heatmap(path.to_string()),
+        _ => Err(format!("unexpected argument {:?}", arg))?,
+    })
+}
+
+fn run_queries(input: &[LineSegment], mode: Rasterisation, queries: &[Query]) -> Result<()> {
+    println!("queries");
+    let coverage = Coverage::new(input);
+    for query in queries {
+        match query {
+            Query::AtLeast(k) => {
+                println!(
+                    "points covered at least {} times ({})",
+                    k,
+                    coverage.count_at_least(*k)
+                );
+            }
+            Query::At(point) => println!("coverage at {:?} ({})", point, coverage.at(*point)),
+            Query::Pairs => {
+                for (i, j, overlap) in overlapping_pairs(input) {
+                    match overlap {
+                        Overlap::Stretch { from, to } => println!(
+                            "segments {} and {} overlap from {:?} to {:?}",
+                            i + 1,
+                            j + 1,
+                            from,
+                            to
+                        ),
+                        Overlap::Crossing(point) => {
+                            println!("segments {} and {} cross at {:?}", i + 1, j + 1, point)
+                        }
+                    }
+                }
+            }
+            Query::Max => {
+                let max = coverage.max();
+                println!(
+                    "highest coverage {} at {} points",
+                    max,
+                    coverage.count_at_least(max.max(1))
+                );
+            }
+            Query::Heatmap(path) => {
+                let heatmap = Heatmap::new(input, mode)?;
+                heatmap.write_image(path)?;
+                println!(
+                    "wrote a {}x{} heatmap from {:?} to {}",
+                    heatmap.width, heatmap.height, heatmap.origin, path
+                );
+            }
+            Query::Ascii => print!("{}", Heatmap::new(input, mode)?.to_ascii()?),
+        }
+    }
+    Ok(())
+}
+
+fn part_1(input: &[LineSegment], raster: bool) {
     println!("part 1");
-    let all_points = input
-        .iter()
-        .flat_map(|&line| points_on_line_part_1(line).into_iter())
-        .collect::<Vec<_>>();
-    println!("duplicates ({})", count_duplicates(&all_points));
+    let count = if raster {
+        let all_points = input
+            .iter()
+            .flat_map(|&line| points_on_line_part_1(line).into_iter())
+            .collect::<Vec<_>>();
+        count_duplicates(&all_points) as u128
+    } else {
+        let segments = input
+            .iter()
+            .copied()
+            .filter(|line| line.is_axis_aligned())
+            .collect::<Vec<_>>();
+        count_overlaps(&segments)
+    };
+    println!("duplicates ({})", count);
 }
 
-fn part_2(input: &[LineSegment]) {
+fn part_2(input: &[LineSegment], mode: Rasterisation, raster: bool) {
     println!("part 2");
-
-    let all_points = input
-        .iter()
-        .flat_map(|&line| points_on_line_part_2(line).into_iter())
-        .collect::<Vec<_>>();
-    println!("duplicates ({})", count_duplicates(&all_points));
+    let count = if raster || mode == Rasterisation::Bresenham {
+        let all_points = input
+            .iter()
+            .flat_map(|&line| points_on_line(line, mode).into_iter())
+            .collect::<Vec<_>>();
+        count_duplicates(&all_points) as u128
+    } else {
+        count_overlaps(input)
+    };
+    println!("duplicates ({})", count);
 }
 
 fn count_duplicates(all_points: &[Point]) -> usize {
@@ -37,10 +173,17 @@ fn count_duplicates(all_points: &[Point]) -> usize {
             duplicates.insert(point);
         }
     }
-    return duplicates.len();
+    duplicates.len()
 }
 
-type Point = (u32, u32);
+/// Coordinates may be negative. They are limited to `-COORDINATE_LIMIT..=COORDINATE_LIMIT`,
+/// or `±L`, so that the difference between two of them, at most `2L`, always fits in
+/// an `i64`. The products taken as `i128` are then of a difference with a coordinate
+/// or with another difference, and a sum of two such products is at most
+/// `2 * (2L)² = 8L²`, which is below `2^127`.
+type Point = (i64, i64);
+
+const COORDINATE_LIMIT: i64 = (1 << 62) - 1;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct LineSegment {
@@ -48,14 +191,523 @@ struct LineSegment {
     to: Point,
 }
 
-fn range(from: u32, to: u32) -> Box<dyn Iterator<Item = u32>> {
-    if from < to {
-        Box::from(from..=to)
+impl LineSegment {
+    fn is_axis_aligned(&self) -> bool {
+        self.from.0 == self.to.0 || self.from.1 == self.to.1
+    }
+
+    /// The segment as `start + k * step` for `k` in `0..=count`, where `step` is the
+    /// smallest whole-number step along it, so these are exactly its lattice points.
+    /// A segment that is a single point gets a horizontal step.
+    fn lattice(&self) -> Lattice {
+        let start = self.from;
+        let (dx, dy) = (self.to.0 - start.0, self.to.1 - start.1);
+        let count = gcd(dx.abs(), dy.abs());
+        let step = if count == 0 {
+            (1, 0)
+        } else {
+            (dx / count, dy / count)
+        };
+        Lattice { start, step, count }
+    }
+}
+
+fn dot((ax, ay): (i64, i64), (bx, by): (i64, i64)) -> i128 {
+    ax as i128 * bx as i128 + ay as i128 * by as i128
+}
+
+fn cross((ax, ay): (i64, i64), (bx, by): (i64, i64)) -> i128 {
+    ax as i128 * by as i128 - ay as i128 * bx as i128
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
     } else {
-        Box::from((to..=from).rev())
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Lattice {
+    start: (i64, i64),
+    step: (i64, i64),
+    count: i64,
+}
+
+/// Identifies the infinite line through a lattice, by its step pointing in a
+/// canonical direction and the value of `step × point`, which is the same for every
+/// point on the line. The step is a difference of coordinates, so this stays within
+/// the bound given for `Point`.
+type LineKey = ((i64, i64), i128);
+
+impl Lattice {
+    fn direction(&self) -> (i64, i64) {
+        let (sx, sy) = self.step;
+        if sx < 0 || (sx == 0 && sy < 0) {
+            (-sx, -sy)
+        } else {
+            (sx, sy)
+        }
+    }
+
+    /// The lattice points of a line between two values of `direction · point`, found
+    /// by stepping from `base`, a lattice point on the line. Stepping keeps every
+    /// product within the bound given for `Point`, where solving for the point from
+    /// `lo` and the line's offset alone would multiply them by `direction` again.
+    fn from_interval(direction: (i64, i64), base: Point, (lo, hi): (i128, i128)) -> Self {
+        let spacing = spacing(direction);
+        // `k * direction` is the difference between two points on a segment
+        let k = ((lo - dot(direction, base)) / spacing) as i64;
+        Lattice {
+            start: (base.0 + k * direction.0, base.1 + k * direction.1),
+            step: direction,
+            count: ((hi - lo) / spacing) as i64,
+        }
+    }
+
+    fn end(&self) -> Point {
+        (
+            self.start.0 + self.step.0 * self.count,
+            self.start.1 + self.step.1 * self.count,
+        )
+    }
+
+    fn line_key(&self) -> LineKey {
+        let (sx, sy) = self.direction();
+        (self.direction(), cross((sx, sy), self.start))
+    }
+
+    /// the range of `direction · point` covered, which steps by `|direction|²`
+    /// between neighbouring lattice points
+    fn extent(&self) -> (i128, i128) {
+        let direction = self.direction();
+        let (a, b) = (dot(direction, self.start), dot(direction, self.end()));
+        (a.min(b), a.max(b))
+    }
+
+    /// the lattice point shared with a lattice that isn't parallel to this one
+    fn crossing(&self, other: &Lattice) -> Option<(i64, i64)> {
+        let denominator = cross(self.step, other.step);
+        if denominator == 0 {
+            return None;
+        }
+        let offset = (other.start.0 - self.start.0, other.start.1 - self.start.1);
+        let k = cross(offset, other.step);
+        let m = cross(offset, self.step);
+        if k % denominator != 0 || m % denominator != 0 {
+            return None;
+        }
+        let (k, m) = (k / denominator, m / denominator);
+        if !(0..=self.count as i128).contains(&k) || !(0..=other.count as i128).contains(&m) {
+            return None;
+        }
+        let k = k as i64;
+        Some((
+            self.start.0 + k * self.step.0,
+            self.start.1 + k * self.step.1,
+        ))
+    }
+}
+
+/// Counts the points covered by at least two segments, treating each segment as its
+/// lattice points. Segments on the same line are merged as intervals along it, and
+/// every other pair meets in at most one point, so the cost depends on the number
+/// of segments and crossings rather than on how long the segments are. A segment
+/// can have more lattice points than a `usize` counts, so they are counted as `u128`.
+fn count_overlaps(segments: &[LineSegment]) -> u128 {
+    let lattices = segments
+        .iter()
+        .map(|line| line.lattice())
+        .collect::<Vec<_>>();
+
+    let mut lines: HashMap<LineKey, Vec<(i128, i128)>> = HashMap::new();
+    let mut bases = HashMap::new();
+    for lattice in &lattices {
+        let key = lattice.line_key();
+        lines.entry(key).or_default().push(lattice.extent());
+        bases.entry(key).or_insert(lattice.start);
+    }
+    let overlaps: HashMap<LineKey, Vec<(i128, i128)>> = lines
+        .into_iter()
+        .map(|(key, extents)| (key, covered_twice(extents)))
+        .filter(|(_, overlaps)| !overlaps.is_empty())
+        .collect();
+    let stretches = overlaps
+        .iter()
+        .flat_map(|(&key, intervals)| {
+            let base = bases[&key];
+            intervals
+                .iter()
+                .map(move |&interval| Lattice::from_interval(key.0, base, interval))
+        })
+        .collect::<Vec<_>>();
+    let mut count = stretches
+        .iter()
+        .map(|stretch| stretch.count as u128 + 1)
+        .sum::<u128>();
+    // a point where overlapping stretches on different lines cross has been counted
+    // once for each of those lines
+    let mut lines_through: HashMap<(i64, i64), HashSet<LineKey>> = HashMap::new();
+    for (idx, a) in stretches.iter().enumerate() {
+        for b in &stretches[idx + 1..] {
+            if let Some(point) = a.crossing(b) {
+                let lines = lines_through.entry(point).or_default();
+                lines.insert(a.line_key());
+                lines.insert(b.line_key());
+            }
+        }
+    }
+    count -= lines_through
+        .values()
+        .map(|lines| lines.len() as u128 - 1)
+        .sum::<u128>();
+
+    let directions = overlaps
+        .keys()
+        .map(|&(direction, _)| direction)
+        .collect::<HashSet<_>>();
+    let in_overlap = |point: Point| {
+        directions.iter().any(|&direction| {
+            overlaps
+                .get(&(direction, cross(direction, point)))
+                .is_some_and(|intervals| {
+                    let position = dot(direction, point);
+                    let idx = intervals.partition_point(|&(_, hi)| hi < position);
+                    intervals.get(idx).is_some_and(|&(lo, _)| lo <= position)
+                })
+        })
+    };
+    let mut crossings = HashSet::new();
+    for (idx, a) in lattices.iter().enumerate() {
+        for b in &lattices[idx + 1..] {
+            if let Some(point) = a.crossing(b) {
+                if !in_overlap(point) {
+                    crossings.insert(point);
+                }
+            }
+        }
+    }
+    count + crossings.len() as u128
+}
+
+/// the sorted, disjoint intervals covered by at least two of the given intervals
+fn covered_twice(extents: Vec<(i128, i128)>) -> Vec<(i128, i128)> {
+    // at the same position, starts are processed before ends so that touching
+    // intervals share their endpoint
+    let mut events = extents
+        .into_iter()
+        .flat_map(|(lo, hi)| [(lo, 0, 1), (hi, 1, -1)])
+        .collect::<Vec<_>>();
+    events.sort_unstable();
+    let mut intervals: Vec<(i128, i128)> = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (position, _, change) in events {
+        let before = depth;
+        depth += change;
+        if before < 2 && depth >= 2 {
+            start = position;
+        } else if before >= 2 && depth < 2 {
+            match intervals.last_mut() {
+                Some(last) if last.1 >= start => last.1 = position,
+                _ => intervals.push((start, position)),
+            }
+        }
+    }
+    intervals
+}
+
+/// how two segments overlap: along a shared stretch of their line, or where they cross
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Overlap {
+    Stretch { from: Point, to: Point },
+    Crossing(Point),
+}
+
+/// Every pair of segments that share at least one lattice point, by their index in
+/// `segments`, with where they overlap.
+fn overlapping_pairs(segments: &[LineSegment]) -> Vec<(usize, usize, Overlap)> {
+    let lattices = segments
+        .iter()
+        .map(|line| line.lattice())
+        .collect::<Vec<_>>();
+    let mut pairs = vec![];
+    for (i, a) in lattices.iter().enumerate() {
+        for (j, b) in lattices.iter().enumerate().skip(i + 1) {
+            let overlap = if a.line_key() == b.line_key() {
+                let ((a_lo, a_hi), (b_lo, b_hi)) = (a.extent(), b.extent());
+                let (lo, hi) = (a_lo.max(b_lo), a_hi.min(b_hi));
+                (lo <= hi).then(|| {
+                    let stretch = Lattice::from_interval(a.direction(), a.start, (lo, hi));
+                    Overlap::Stretch {
+                        from: stretch.start,
+                        to: stretch.end(),
+                    }
+                })
+            } else {
+                a.crossing(b).map(Overlap::Crossing)
+            };
+            if let Some(overlap) = overlap {
+                pairs.push((i, j, overlap));
+            }
+        }
+    }
+    pairs
+}
+
+/// a stretch of a line, as `start..end` in `position`, covered by `depth` segments
+/// on that line
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    start: i128,
+    end: i128,
+    depth: usize,
+}
+
+/// How many segments cover each lattice point, worked out from the segments rather
+/// than by listing points. A point that isn't a crossing of segments on different
+/// lines is covered only by segments on its own line, so along each line coverage
+/// is constant between segment ends. The crossings are the only other points to
+/// track.
+struct Coverage {
+    /// the lines with at least one segment, each split into runs of equal coverage
+    lines: HashMap<LineKey, Vec<Run>>,
+    directions: Vec<(i64, i64)>,
+    crossings: HashSet<Point>,
+}
+
+impl Coverage {
+    fn new(segments: &[LineSegment]) -> Self {
+        let lattices = segments
+            .iter()
+            .map(|line| line.lattice())
+            .collect::<Vec<_>>();
+        // each segment covers `lo..hi + 1`, so that touching segments share a point
+        let mut events: HashMap<LineKey, Vec<(i128, isize)>> = HashMap::new();
+        for lattice in &lattices {
+            let key = lattice.line_key();
+            let (lo, hi) = lattice.extent();
+            let line_events = events.entry(key).or_default();
+            line_events.push((position(key.0, lo), 1));
+            line_events.push((position(key.0, hi) + 1, -1));
+        }
+        let lines = events
+            .into_iter()
+            .map(|(key, events)| (key, runs(events)))
+            .collect::<HashMap<_, _>>();
+        let directions = lines
+            .keys()
+            .map(|&(direction, _)| direction)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut crossings = HashSet::new();
+        for (idx, a) in lattices.iter().enumerate() {
+            for b in &lattices[idx + 1..] {
+                crossings.extend(a.crossing(b));
+            }
+        }
+        Coverage {
+            lines,
+            directions,
+            crossings,
+        }
+    }
+
+    /// the coverage contributed at a point by each line through it
+    fn depths_at(&self, point: Point) -> impl Iterator<Item = usize> + '_ {
+        self.directions.iter().filter_map(move |&direction| {
+            let runs = self.lines.get(&(direction, cross(direction, point)))?;
+            let position = position(direction, dot(direction, point));
+            let idx = runs.partition_point(|run| run.end <= position);
+            runs.get(idx)
+                .filter(|run| run.start <= position)
+                .map(|run| run.depth)
+        })
+    }
+
+    /// the number of segments covering a point
+    fn at(&self, point: Point) -> usize {
+        self.depths_at(point).sum()
+    }
+
+    /// the number of points covered by at least `k` segments, for `k` of at least 1
+    fn count_at_least(&self, k: usize) -> u128 {
+        let mut count = self
+            .lines
+            .values()
+            .flat_map(|runs| runs.iter().filter(|run| run.depth >= k))
+            .map(|run| (run.end - run.start) as u128)
+            .sum::<u128>();
+        // each line through a crossing counted it by that line's coverage alone
+        for &point in &self.crossings {
+            let depths = self.depths_at(point).collect::<Vec<_>>();
+            count -= depths.iter().filter(|&&depth| depth >= k).count() as u128;
+            if depths.iter().sum::<usize>() >= k {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// the highest coverage of any point
+    fn max(&self) -> usize {
+        let along_lines = self
+            .lines
+            .values()
+            .flatten()
+            .map(|run| run.depth)
+            .max()
+            .unwrap_or(0);
+        let at_crossings = self
+            .crossings
+            .iter()
+            .map(|&point| self.at(point))
+            .max()
+            .unwrap_or(0);
+        along_lines.max(at_crossings)
     }
 }
 
+/// the distance in `direction · point` between neighbouring lattice points on a line
+fn spacing(direction: (i64, i64)) -> i128 {
+    dot(direction, direction)
+}
+
+/// Numbers the lattice points along a line one after another from their value of
+/// `direction · point`, which steps by `spacing`. Every value on one line leaves the
+/// same remainder, so dividing and rounding down keeps them apart.
+fn position(direction: (i64, i64), along: i128) -> i128 {
+    along.div_euclid(spacing(direction))
+}
+
+/// splits a line into runs of equal coverage from where segments start and stop
+fn runs(mut events: Vec<(i128, isize)>) -> Vec<Run> {
+    events.sort_unstable();
+    let mut runs = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (position, change) in events {
+        if depth > 0 && position > start {
+            runs.push(Run {
+                start,
+                end: position,
+                depth: depth as usize,
+            });
+        }
+        depth += change;
+        start = position;
+    }
+    runs
+}
+
+/// the most cells a heatmap may have
+const MAX_HEATMAP_CELLS: i128 = 1 << 26;
+
+/// the widest heatmap that is drawn as text
+const MAX_ASCII_WIDTH: usize = 120;
+
+/// Coverage per cell over the bounding box of the segments, with `x` across and `y`
+/// down, from the points each segment covers under a rasterisation.
+struct Heatmap {
+    origin: Point,
+    width: usize,
+    height: usize,
+    cells: Vec<u32>,
+}
+
+impl Heatmap {
+    fn new(segments: &[LineSegment], mode: Rasterisation) -> Result<Self> {
+        let corners = segments.iter().flat_map(|line| [line.from, line.to]);
+        let (min_x, max_x) = min_max(corners.clone().map(|(x, _)| x)).ok_or("no segments")?;
+        let (min_y, max_y) = min_max(corners.map(|(_, y)| y)).ok_or("no segments")?;
+        let width = (max_x - min_x) as i128 + 1;
+        let height = (max_y - min_y) as i128 + 1;
+        if width * height > MAX_HEATMAP_CELLS {
+            Err(format!(
+                "a {}x{} heatmap has more than {} cells",
+                width, height, MAX_HEATMAP_CELLS
+            ))?
+        }
+        let (width, height) = (width as usize, height as usize);
+        let mut cells = vec![0; width * height];
+        for &line in segments {
+            for (x, y) in points_on_line(line, mode) {
+                cells[(y - min_y) as usize * width + (x - min_x) as usize] += 1;
+            }
+        }
+        Ok(Heatmap {
+            origin: (min_x, min_y),
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// cells as greyscale bytes, from black for no coverage to white for the most
+    fn greyscale(&self) -> Vec<u8> {
+        let max = self.cells.iter().copied().max().unwrap_or(0).max(1) as u64;
+        self.cells
+            .iter()
+            .map(|&count| (count as u64 * 255 / max) as u8)
+            .collect()
+    }
+
+    /// writes a binary PGM or a PNG image, depending on the file's extension
+    fn write_image(&self, path: &str) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        if path.ends_with(".png") {
+            let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()?
+                .write_image_data(&self.greyscale())?;
+        } else if path.ends_with(".pgm") {
+            let mut file = file;
+            write!(file, "P5\n{} {}\n255\n", self.width, self.height)?;
+            file.write_all(&self.greyscale())?;
+        } else {
+            Err(format!("expected a .pgm or .png file, not {:?}", path))?
+        }
+        Ok(())
+    }
+
+    /// the heatmap as in the puzzle's example, with `.` for no coverage and `+` for
+    /// more than 9
+    fn to_ascii(&self) -> Result<String> {
+        if self.width > MAX_ASCII_WIDTH {
+            Err(format!(
+                "a heatmap {} cells wide is too wide to draw as text",
+                self.width
+            ))?
+        }
+        let mut text = String::new();
+        for row in self.cells.chunks(self.width) {
+            text.extend(row.iter().map(|&count| match count {
+                0 => '.',
+                1..=9 => char::from_digit(count, 10).unwrap(),
+                _ => '+',
+            }));
+            text.push('\n');
+        }
+        Ok(text)
+    }
+}
+
+fn min_max(values: impl Iterator<Item = i64>) -> Option<(i64, i64)> {
+    values.fold(None, |bounds, value| match bounds {
+        None => Some((value, value)),
+        Some((lo, hi)) => Some((lo.min(value), hi.max(value))),
+    })
+}
+
+fn range(from: i64, to: i64) -> impl Iterator<Item = i64> {
+    let step = if from <= to { 1 } else { -1 };
+    (0..=from.abs_diff(to)).map(move |k| from + step * k as i64)
+}
+
 fn points_on_line_part_1(line: LineSegment) -> Vec<Point> {
     let LineSegment {
         from: (from_x, from_y),
@@ -70,22 +722,62 @@ fn points_on_line_part_1(line: LineSegment) -> Vec<Point> {
     }
 }
 
-fn points_on_line_part_2(line: LineSegment) -> Vec<Point> {
-    let LineSegment {
-        from: (from_x, from_y),
-        to: (to_x, to_y),
-    } = line;
-    if from_x == to_x {
-        range(from_y, to_y).map(|y| (from_x, y)).collect()
-    } else if from_y == to_y {
-        range(from_x, to_x).map(|x| (x, from_y)).collect()
-    } else {
-        let range = range(from_x, to_x).zip(range(from_y, to_y)).collect();
-        println!("diagonal {:?} = {:?}", &line, range);
-        range
+/// which points a segment that isn't horizontal or vertical covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rasterisation {
+    /// only the points that lie exactly on the segment
+    Lattice,
+    /// one point per step along the longer axis, nearest to the segment, as drawn by
+    /// Bresenham's line algorithm from `from` to `to`
+    Bresenham,
+}
+
+fn points_on_line(line: LineSegment, mode: Rasterisation) -> Vec<Point> {
+    if line.is_axis_aligned() {
+        return points_on_line_part_1(line);
+    }
+    match mode {
+        Rasterisation::Lattice => {
+            let Lattice { start, step, count } = line.lattice();
+            (0..=count)
+                .map(|k| (start.0 + k * step.0, start.1 + k * step.1))
+                .collect()
+        }
+        Rasterisation::Bresenham => bresenham(line),
     }
 }
 
+fn bresenham(line: LineSegment) -> Vec<Point> {
+    let ((mut x, mut y), (to_x, to_y)) = (line.from, line.to);
+    let (dx, dy) = ((to_x - x).abs() as i128, -(to_y - y).abs() as i128);
+    let (step_x, step_y) = ((to_x - x).signum(), (to_y - y).signum());
+    let mut error = dx + dy;
+    let mut points = vec![];
+    loop {
+        points.push((x, y));
+        if (x, y) == (to_x, to_y) {
+            return points;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+fn parse_coordinate(coordinate: &str) -> Result<i64> {
+    let coordinate: i64 = coordinate.trim().parse()?;
+    if coordinate.unsigned_abs() > COORDINATE_LIMIT as u64 {
+        Err(format!("coordinate {} is out of range", coordinate))?
+    }
+    Ok(coordinate)
+}
+
 fn read_input() -> Result<Vec<LineSegment>> {
     let mut line_segments = vec![];
     let stdin = io::stdin();
@@ -97,9 +789,196 @@ fn read_input() -> Result<Vec<LineSegment>> {
             .ok_or("failed to split from part")?;
         let (to_x, to_y) = to_str.split_once(",").ok_or("failed to split to part")?;
         line_segments.push(LineSegment {
-            from: (from_x.parse()?, from_y.parse()?),
-            to: (to_x.parse()?, to_y.parse()?),
+            from: (parse_coordinate(from_x)?, parse_coordinate(from_y)?),
+            to: (parse_coordinate(to_x)?, parse_coordinate(to_y)?),
         });
     }
     Ok(line_segments)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        count_overlaps, overlapping_pairs, points_on_line, Coverage, Heatmap, LineSegment, Overlap,
+        Rasterisation, COORDINATE_LIMIT,
+    };
+
+    fn segment(from: (i64, i64), to: (i64, i64)) -> LineSegment {
+        LineSegment { from, to }
+    }
+
+    #[test]
+    fn overlapping_stretches_that_cross() {
+        // two diagonals overlap along y = x - 57, and two verticals overlap along
+        // x = 731, with both stretches passing through (731, 674)
+        let segments = [
+            segment((834, 777), (304, 247)),
+            segment((591, 534), (923, 866)),
+            segment((731, 719), (731, 494)),
+            segment((731, 370), (731, 872)),
+        ];
+        assert_eq!(count_overlaps(&segments), 244 + 226 - 1);
+    }
+
+    #[test]
+    fn long_segments() {
+        let segments = [
+            segment((0, 0), (4_000_000, 4_000_000)),
+            segment((1_000_000, 1_000_000), (3_000_000, 3_000_000)),
+            segment((0, 2_000_000), (4_000_000, 2_000_000)),
+            segment((0, 4_000_000), (4_000_000, 0)),
+            segment((3_999_999, 0), (3_999_999, 4_000_000)),
+        ];
+        // every other crossing is at (2000000, 2000000), inside the diagonal overlap
+        assert_eq!(count_overlaps(&segments), 2_000_001 + 3);
+    }
+
+    #[test]
+    fn signed_coordinates() {
+        let segments = [
+            segment((-3, -3), (3, 3)),
+            segment((-3, 3), (3, -3)),
+            segment((-1, -1), (5, 5)),
+        ];
+        assert_eq!(count_overlaps(&segments), 5);
+
+        let limit = COORDINATE_LIMIT;
+        let segments = [
+            segment((-limit, -limit), (limit, limit)),
+            segment((limit, limit), (0, 0)),
+            segment((-5, -limit), (-5, limit)),
+        ];
+        assert_eq!(count_overlaps(&segments), limit as u128 + 2);
+    }
+
+    #[test]
+    fn totals_beyond_usize() {
+        // three full-length lines through the origin, each given twice, have more
+        // points covered twice than a u64 can count
+        let limit = COORDINATE_LIMIT;
+        let lines = [
+            segment((-limit, -limit), (limit, limit)),
+            segment((-limit, 0), (limit, 0)),
+            segment((0, limit), (0, -limit)),
+        ];
+        let segments = [lines, lines].concat();
+        let points = 2 * limit as u128 + 1;
+        assert_eq!(count_overlaps(&segments), 3 * points - 2);
+        assert_eq!(count_overlaps(&segments[1..]), 2 * points - 1);
+        let coverage = Coverage::new(&segments);
+        assert_eq!(coverage.count_at_least(2), 3 * points - 2);
+        assert_eq!(coverage.count_at_least(3), 1);
+        assert_eq!(coverage.at((0, 0)), 6);
+        assert_eq!(coverage.max(), 6);
+    }
+
+    #[test]
+    fn steep_lines_at_the_limit() {
+        let limit = COORDINATE_LIMIT;
+        let steep = segment((0, -limit), (1, limit - 1));
+        assert_eq!(count_overlaps(&[steep, steep]), 2);
+        let pairs = overlapping_pairs(&[steep, steep]);
+        assert_eq!(
+            pairs,
+            vec![(
+                0,
+                1,
+                Overlap::Stretch {
+                    from: (0, -limit),
+                    to: (1, limit - 1)
+                }
+            )]
+        );
+        let long = segment((-limit, -limit), (limit, limit - 1));
+        let reversed = segment(long.to, long.from);
+        assert_eq!(count_overlaps(&[long, reversed, steep]), 2);
+    }
+
+    #[test]
+    fn any_slope() {
+        let line = segment((6, 4), (0, 0));
+        assert_eq!(
+            points_on_line(line, Rasterisation::Lattice),
+            vec![(6, 4), (3, 2), (0, 0)]
+        );
+        let line = segment((0, 0), (5, 2));
+        assert_eq!(
+            points_on_line(line, Rasterisation::Bresenham),
+            vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]
+        );
+    }
+
+    fn example() -> Vec<LineSegment> {
+        include_str!("test.txt")
+            .lines()
+            .map(|line| {
+                let (from, to) = line.split_once(" -> ").unwrap();
+                let point = |p: &str| {
+                    let (x, y) = p.split_once(',').unwrap();
+                    (x.parse().unwrap(), y.parse().unwrap())
+                };
+                segment(point(from), point(to))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn coverage_queries() {
+        let segments = example();
+        let coverage = Coverage::new(&segments);
+        assert_eq!(coverage.count_at_least(2), 12);
+        assert_eq!(coverage.count_at_least(3), 2);
+        assert_eq!(coverage.max(), 3);
+        assert_eq!(coverage.at((4, 4)), 3);
+        assert_eq!(coverage.at((2, 9)), 2);
+        assert_eq!(coverage.at((9, 9)), 0);
+
+        let pairs = overlapping_pairs(&segments);
+        assert!(pairs.contains(&(
+            0,
+            6,
+            Overlap::Stretch {
+                from: (0, 9),
+                to: (2, 9)
+            }
+        )));
+        assert!(pairs.contains(&(1, 8, Overlap::Crossing((4, 4)))));
+    }
+
+    #[test]
+    fn coverage_at_the_limit() {
+        let limit = COORDINATE_LIMIT;
+        let long = segment((-limit, -limit), (limit, limit - 1));
+        let coverage = Coverage::new(&[long]);
+        assert_eq!(coverage.count_at_least(1), 2);
+        assert_eq!(coverage.at((limit, limit - 1)), 1);
+        assert_eq!(coverage.at((0, 0)), 0);
+        assert_eq!(coverage.max(), 1);
+
+        let steep = segment((0, -limit), (1, limit - 1));
+        let diagonal = segment((-limit, -limit), (limit, limit));
+        let coverage = Coverage::new(&[steep, steep, diagonal, long]);
+        assert_eq!(coverage.count_at_least(2), 3);
+        assert_eq!(coverage.at((0, -limit)), 2);
+        assert_eq!(coverage.at((-limit, -limit)), 2);
+        assert_eq!(coverage.max(), 2);
+    }
+
+    #[test]
+    fn example_heatmap() {
+        let heatmap = Heatmap::new(&example(), Rasterisation::Lattice).unwrap();
+        assert_eq!(
+            heatmap.to_ascii().unwrap(),
+            "1.1....11.\n\
+             .111...2..\n\
+             ..2.1.111.\n\
+             ...1.2.2..\n\
+             .112313211\n\
+             ...1.2....\n\
+             ..1...1...\n\
+             .1.....1..\n\
+             1.......1.\n\
+             222111....\n"
+        );
+    }
+}
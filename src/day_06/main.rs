@@ -1,57 +1,541 @@
-use std::collections::HashMap;
-use std::io;
+use num_bigint::BigUint;
+use std::io::{self, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The life cycle and the number of days for each part can be set with `key=value`
+/// arguments, or with a `config=<file>` argument naming a file of `key = value`
+/// lines, where blank lines and lines starting with `#` are ignored. Arguments are
+/// applied in order, so later ones override earlier ones. The keys are
+/// - `reset`: the timer a fish restarts from after spawning, 6 by default
+/// - `newborn`: the timer a newborn fish starts from, 8 by default
+/// - `offspring`: how many fish each fish spawns at a time, 1 by default
+/// - `part1-days` and `part2-days`: 80 and 256 by default
+/// - `fast-days`: also counts the fish after this many days, up to 10^18, by
+///   raising the daily transition matrix to that power
+/// - `modulo`: gives the `fast-days` count modulo this number, usually a prime,
+///   instead of exactly, which needs a number of digits proportional to the days
+/// - `timeline`: writes the count in each timer bucket and the total for every day
+///   of part two to this CSV file
+/// - `chart`: draws the total for every day of part two on a log scale, as text
+///   for `ascii` or as an image for a `.svg` file
+/// - `target`: finds the first day the population reaches at least this many fish,
+///   which may be a number of any size or written as `<base>^<exponent>`
+///
+/// e.g.
+///   cargo run --bin day_06 < input.txt config=species.txt part2-days=100
+///   cargo run --bin day_06 < input.txt fast-days=1000000000000000000 modulo=1000000007
+///   cargo run --bin day_06 < input.txt timeline=fish.csv chart=ascii
+///   cargo run --bin day_06 < input.txt target=10^100
 fn main() -> Result<()> {
+    let mut config = Config::default();
+    for arg in std::env::args().skip(1) {
+        config.apply_arg(&arg)?;
+    }
     let fishies = read_input()?;
-    part_1(&fishies);
-    part_2(&fishies);
+    part_1(&fishies, &config);
+    part_2(&fishies, &config)?;
+    if let Some(days) = config.fast_days {
+        part_fast(&fishies, &config.life_cycle, days, config.modulo);
+    }
+    if let Some(target) = &config.target {
+        part_target(&fishies, &config.life_cycle, target);
+    }
 
     Ok(())
 }
 
-fn part_1(fishies: &[u8]) {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LifeCycle {
+    reset: u8,
+    newborn: u8,
+    offspring: u64,
+}
+
+impl Default for LifeCycle {
+    fn default() -> Self {
+        LifeCycle {
+            reset: 6,
+            newborn: 8,
+            offspring: 1,
+        }
+    }
+}
+
+impl LifeCycle {
+    /// the new timer of a fish, and how many fish it spawns
+    fn update_age(&self, initial_age: u8) -> (u8, u64) {
+        if initial_age == 0 {
+            (self.reset, self.offspring)
+        } else {
+            (initial_age - 1, 0)
+        }
+    }
+
+    /// the number of distinct timer values a school can have, which is enough for
+    /// its initial fish as well as for the ones that reset or are born
+    fn buckets(&self, fishies: &[u8]) -> usize {
+        let oldest = fishies.iter().copied().max().unwrap_or(0);
+        oldest.max(self.reset).max(self.newborn) as usize + 1
+    }
+}
+
+fn parse_target(target: &str) -> Result<BigUint> {
+    Ok(match target.split_once('^') {
+        Some((base, exponent)) => base.parse::<BigUint>()?.pow(exponent.parse::<u32>()?),
+        None => target.parse()?,
+    })
+}
+
+#[derive(Debug)]
+struct Config {
+    life_cycle: LifeCycle,
+    part_1_days: u32,
+    part_2_days: u32,
+    fast_days: Option<u64>,
+    modulo: Option<u64>,
+    timeline: Option<String>,
+    chart: Option<String>,
+    target: Option<BigUint>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            life_cycle: LifeCycle::default(),
+            part_1_days: 80,
+            part_2_days: 256,
+            fast_days: None,
+            modulo: None,
+            timeline: None,
+            chart: None,
+            target: None,
+        }
+    }
+}
+
+impl Config {
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "reset" => self.life_cycle.reset = value.parse()?,
+            "newborn" => self.life_cycle.newborn = value.parse()?,
+            "offspring" => self.life_cycle.offspring = value.parse()?,
+            "part1-days" => self.part_1_days = value.parse()?,
+            "part2-days" => self.part_2_days = value.parse()?,
+            "fast-days" => self.fast_days = Some(value.parse()?),
+            "modulo" => match value.parse()? {
+                0 => Err("modulo must be at least 1")?,
+                modulus => self.modulo = Some(modulus),
+            },
+            "timeline" => self.timeline = Some(value.to_string()),
+            "chart" => match value {
+                "ascii" => self.chart = Some(value.to_string()),
+                path if path.ends_with(".svg") => self.chart = Some(value.to_string()),
+                _ => Err(format!("expected ascii or a .svg file, not {:?}", value))?,
+            },
+            "target" => self.target = Some(parse_target(value)?),
+            key => Err(format!("unknown setting {:?}", key))?,
+        }
+        Ok(())
+    }
+
+    fn apply_arg(&mut self, arg: &str) -> Result<()> {
+        match arg.split_once('=') {
+            Some(("config", path)) => self.apply_file(path),
+            Some((key, value)) => self.set(key, value),
+            None => Err(format!("expected key=value, not {:?}", arg))?,
+        }
+    }
+
+    fn apply_file(&mut self, path: &str) -> Result<()> {
+        for (idx, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or(format!(
+                "{}:{}: expected key = value",
+                path,
+                idx + 1
+            ))?;
+            self.set(key.trim(), value.trim())
+                .map_err(|err| format!("{}:{}: {}", path, idx + 1, err))?;
+        }
+        Ok(())
+    }
+}
+
+fn part_1(fishies: &[u8], config: &Config) {
     println!("part 1");
-    let mut fishies: Vec<_> = fishies.into_iter().map(|&f| f).collect();
-    for _ in 0..80 {
+    let fishies = simulate(fishies, &config.life_cycle, config.part_1_days);
+    println!("{} fishies", fishies.len());
+}
+
+/// follows every fish individually
+fn simulate(fishies: &[u8], life_cycle: &LifeCycle, days: u32) -> Vec<u8> {
+    let mut fishies: Vec<_> = fishies.to_vec();
+    for _ in 0..days {
         fishies = fishies.iter().fold(vec![], |mut acc, &fishie| {
-            let (new_age, spawn) = update_age(fishie);
+            let (new_age, spawn) = life_cycle.update_age(fishie);
             acc.push(new_age);
-            if let Some(new_fishie) = spawn {
-                acc.push(new_fishie);
+            for _ in 0..spawn {
+                acc.push(life_cycle.newborn);
             }
             acc
         })
     }
-    println!("{} fishies", fishies.len());
+    fishies
 }
 
-fn part_2(fishies: &[u8]) {
-    println!("part 2");
-    let mut age_to_count: HashMap<u8, u64> = HashMap::new();
+/// the number of fish with each timer value, with as many buckets as the life cycle
+/// needs
+fn count_ages(fishies: &[u8], life_cycle: &LifeCycle) -> Vec<u64> {
+    let mut age_to_count = vec![0; life_cycle.buckets(fishies)];
     for &fishie in fishies {
-        *age_to_count.entry(fishie).or_insert(0) += 1;
-    }
-    for _i in 0..256 {
-        let &count_spawn = age_to_count.get(&0).unwrap_or(&0);
-        age_to_count = age_to_count
-            .into_iter()
-            .filter_map(|(age, count)| (age != 0).then(|| (age - 1, count)))
-            .collect();
-        *age_to_count.entry(6).or_insert(0) += count_spawn;
-        age_to_count.insert(8, count_spawn);
-    }
-    let total: u64 = age_to_count.iter().map(|(_, &count)| count as u64).sum();
+        age_to_count[fishie as usize] += 1;
+    }
+    age_to_count
+}
+
+/// moves every count on by a day, or gives `None` if a count overflows
+fn next_day(age_to_count: &[u64], life_cycle: &LifeCycle) -> Option<Vec<u64>> {
+    let count_spawn = age_to_count[0];
+    let mut next = age_to_count[1..].to_vec();
+    next.push(0);
+    let reset = &mut next[life_cycle.reset as usize];
+    *reset = reset.checked_add(count_spawn)?;
+    let newborn = &mut next[life_cycle.newborn as usize];
+    *newborn = newborn.checked_add(count_spawn.checked_mul(life_cycle.offspring)?)?;
+    Some(next)
+}
+
+fn part_2(fishies: &[u8], config: &Config) -> Result<()> {
+    println!("part 2");
+    let timeline = timeline(fishies, &config.life_cycle, config.part_2_days)?;
+    let (_, total) = timeline.last().expect("the timeline starts on day 0");
     println!("{} fishies", total);
+    if let Some(path) = &config.timeline {
+        write_timeline_csv(&timeline, path)?;
+    }
+    match config.chart.as_deref() {
+        None => {}
+        Some("ascii") => print!("{}", ascii_chart(&timeline)),
+        Some(path) => std::fs::write(path, svg_chart(&timeline))?,
+    }
+    Ok(())
+}
+
+/// the count in each timer bucket and the total, for every day from 0 to `days`
+fn timeline(fishies: &[u8], life_cycle: &LifeCycle, days: u32) -> Result<Vec<(Vec<u64>, u64)>> {
+    let total = |age_to_count: &[u64]| {
+        age_to_count
+            .iter()
+            .try_fold(0u64, |total, &count| total.checked_add(count))
+    };
+    let mut age_to_count = count_ages(fishies, life_cycle);
+    let mut timeline = Vec::with_capacity(days as usize + 1);
+    for day in 0..=days {
+        if day > 0 {
+            age_to_count = next_day(&age_to_count, life_cycle)
+                .ok_or(format!("fish counts overflow on day {}", day))?;
+        }
+        let total =
+            total(&age_to_count).ok_or(format!("total fish count overflows on day {}", day))?;
+        timeline.push((age_to_count.clone(), total));
+    }
+    Ok(timeline)
+}
+
+fn write_timeline_csv(timeline: &[(Vec<u64>, u64)], path: &str) -> Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let buckets = timeline
+        .first()
+        .map_or(0, |(age_to_count, _)| age_to_count.len());
+    write!(file, "day")?;
+    for age in 0..buckets {
+        write!(file, ",age_{}", age)?;
+    }
+    writeln!(file, ",total")?;
+    for (day, (age_to_count, total)) in timeline.iter().enumerate() {
+        write!(file, "{}", day)?;
+        for count in age_to_count {
+            write!(file, ",{}", count)?;
+        }
+        writeln!(file, ",{}", total)?;
+    }
+    Ok(())
 }
 
-fn update_age(initial_age: u8) -> (u8, Option<u8>) {
-    if initial_age == 0 {
-        (6, Some(8))
-    } else {
-        (initial_age - 1, None)
+/// the most rows and the widest bar in the text chart
+const ASCII_CHART_ROWS: usize = 64;
+const ASCII_CHART_WIDTH: usize = 60;
+
+/// log10 of each day's total, taking an empty school as 0
+fn log_totals(timeline: &[(Vec<u64>, u64)]) -> Vec<f64> {
+    timeline
+        .iter()
+        .map(|&(_, total)| (total.max(1) as f64).log10())
+        .collect()
+}
+
+/// one row per day, or per few days for long timelines, with a bar whose length is
+/// proportional to the logarithm of the total
+fn ascii_chart(timeline: &[(Vec<u64>, u64)]) -> String {
+    let logs = log_totals(timeline);
+    let top = logs.iter().copied().fold(1.0, f64::max);
+    let every = timeline.len().div_ceil(ASCII_CHART_ROWS).max(1);
+    let mut chart = format!("log10(total) from 0 to {:.1}\n", top);
+    for day in (0..timeline.len()).step_by(every) {
+        let bar = (logs[day] / top * ASCII_CHART_WIDTH as f64).round() as usize;
+        chart += &format!(
+            "{:>5} |{:<width$}| {}\n",
+            day,
+            "#".repeat(bar),
+            timeline[day].1,
+            width = ASCII_CHART_WIDTH
+        );
     }
+    chart
+}
+
+/// the total on a log scale as a line, with a grid line at each power of ten
+fn svg_chart(timeline: &[(Vec<u64>, u64)]) -> String {
+    let (width, height, margin) = (800.0, 400.0, 50.0);
+    let logs = log_totals(timeline);
+    let top = logs.iter().copied().fold(1.0, f64::max).ceil();
+    let last_day = (timeline.len() - 1).max(1) as f64;
+    let x = |day: f64| margin + day / last_day * (width - 2.0 * margin);
+    let y = |log: f64| height - margin - log / top * (height - 2.0 * margin);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        width, height
+    );
+    for power in 0..=top as u32 {
+        let grid_y = y(power as f64);
+        svg += &format!(
+            "<line x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"#ddd\"/>\n\
+             <text x=\"{}\" y=\"{:.1}\" font-size=\"10\" text-anchor=\"end\">1e{}</text>\n",
+            margin,
+            grid_y,
+            width - margin,
+            grid_y,
+            margin - 4.0,
+            grid_y + 3.0,
+            power
+        );
+    }
+    let points = logs
+        .iter()
+        .enumerate()
+        .map(|(day, &log)| format!("{:.1},{:.1}", x(day as f64), y(log)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    svg += &format!(
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"2\"/>\n\
+         <text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"middle\">day 0 to {}</text>\n\
+         </svg>\n",
+        points,
+        width / 2.0,
+        height - margin / 3.0,
+        timeline.len() - 1
+    );
+    svg
+}
+
+fn part_fast(fishies: &[u8], life_cycle: &LifeCycle, days: u64, modulo: Option<u64>) {
+    println!("after {} days", days);
+    match modulo {
+        Some(modulus) => {
+            let total = count_after_many_days(&Modulo(modulus), fishies, life_cycle, days);
+            println!("{} fishies modulo {}", total, modulus);
+        }
+        None => {
+            let total = count_after_many_days(&Exact, fishies, life_cycle, days);
+            println!("{} fishies", total);
+        }
+    }
+}
+
+/// the numbers counts are kept in
+trait Arithmetic {
+    type Value: Clone;
+
+    fn value(&self, n: u64) -> Self::Value;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// counts modulo a number
+struct Modulo(u64);
+
+impl Arithmetic for Modulo {
+    type Value = u64;
+
+    fn value(&self, n: u64) -> u64 {
+        n % self.0
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+/// exact counts of any size
+struct Exact;
+
+impl Arithmetic for Exact {
+    type Value = BigUint;
+
+    fn value(&self, n: u64) -> BigUint {
+        BigUint::from(n)
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a + b
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b
+    }
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+/// The matrix taking the counts for one day to the next: every timer moves down a
+/// bucket, and the fish in bucket 0 go to the reset bucket and spawn into the
+/// newborn bucket.
+fn transition<A: Arithmetic>(
+    arith: &A,
+    life_cycle: &LifeCycle,
+    buckets: usize,
+) -> Matrix<A::Value> {
+    let mut matrix = vec![vec![0; buckets]; buckets];
+    for (age, row) in matrix.iter_mut().enumerate().take(buckets - 1) {
+        row[age + 1] = 1;
+    }
+    matrix[life_cycle.reset as usize][0] += 1;
+    matrix[life_cycle.newborn as usize][0] += life_cycle.offspring;
+    matrix
+        .into_iter()
+        .map(|row| row.into_iter().map(|n| arith.value(n)).collect())
+        .collect()
+}
+
+fn mat_mul<A: Arithmetic>(
+    arith: &A,
+    a: &Matrix<A::Value>,
+    b: &Matrix<A::Value>,
+) -> Matrix<A::Value> {
+    (0..a.len())
+        .map(|i| {
+            (0..b[0].len())
+                .map(|j| {
+                    (0..b.len()).fold(arith.value(0), |sum, k| {
+                        arith.add(&sum, &arith.mul(&a[i][k], &b[k][j]))
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn mat_vec_mul<A: Arithmetic>(arith: &A, a: &Matrix<A::Value>, v: &[A::Value]) -> Vec<A::Value> {
+    a.iter()
+        .map(|row| {
+            row.iter().zip(v).fold(arith.value(0), |sum, (x, y)| {
+                arith.add(&sum, &arith.mul(x, y))
+            })
+        })
+        .collect()
+}
+
+/// The number of fish after some days, by applying the transition matrix raised to
+/// the number of days to the initial counts. The matrix is squared once per bit of
+/// `days`, so this takes O(log days) matrix products.
+fn count_after_many_days<A: Arithmetic>(
+    arith: &A,
+    fishies: &[u8],
+    life_cycle: &LifeCycle,
+    days: u64,
+) -> A::Value {
+    let mut counts = count_ages(fishies, life_cycle)
+        .into_iter()
+        .map(|n| arith.value(n))
+        .collect::<Vec<_>>();
+    let mut power = transition(arith, life_cycle, counts.len());
+    let mut days = days;
+    while days > 0 {
+        if days & 1 == 1 {
+            counts = mat_vec_mul(arith, &power, &counts);
+        }
+        days >>= 1;
+        if days > 0 {
+            power = mat_mul(arith, &power, &power);
+        }
+    }
+    counts
+        .iter()
+        .fold(arith.value(0), |total, count| arith.add(&total, count))
+}
+
+fn part_target(fishies: &[u8], life_cycle: &LifeCycle, target: &BigUint) {
+    println!("target of {} fishies", target);
+    match days_until(fishies, life_cycle, target) {
+        Some((day, age_to_count)) => {
+            println!("reached on day {}", day);
+            for (age, count) in age_to_count.iter().enumerate() {
+                println!("age {}: {}", age, count);
+            }
+            println!("total: {}", age_to_count.iter().sum::<BigUint>());
+        }
+        None => println!("never reached"),
+    }
+}
+
+/// The first day on which there are at least `target` fish, with the count in each
+/// timer bucket on that day, or `None` if the school never gets that big. No fish
+/// ever dies, so the total never falls. The day is found by squaring the transition
+/// matrix until a power of two days is enough, then binary searching back down
+/// through those powers, which takes O(log days) matrix products.
+fn days_until(
+    fishies: &[u8],
+    life_cycle: &LifeCycle,
+    target: &BigUint,
+) -> Option<(u64, Vec<BigUint>)> {
+    let total = |counts: &[BigUint]| counts.iter().sum::<BigUint>();
+    let mut age_to_count = count_ages(fishies, life_cycle)
+        .into_iter()
+        .map(BigUint::from)
+        .collect::<Vec<_>>();
+    if total(&age_to_count) >= *target {
+        return Some((0, age_to_count));
+    }
+    if life_cycle.offspring == 0 || fishies.is_empty() {
+        return None;
+    }
+    // powers[k] moves the counts on by 2^k days
+    let mut powers = vec![transition(&Exact, life_cycle, age_to_count.len())];
+    loop {
+        let last = powers.last().unwrap();
+        if total(&mat_vec_mul(&Exact, last, &age_to_count)) >= *target {
+            break;
+        }
+        powers.push(mat_mul(&Exact, last, last));
+    }
+    // find the last day that is still short of the target
+    let mut day = 0u64;
+    for (k, power) in powers.iter().enumerate().rev() {
+        let next = mat_vec_mul(&Exact, power, &age_to_count);
+        if total(&next) < *target {
+            age_to_count = next;
+            day += 1 << k;
+        }
+    }
+    Some((day + 1, mat_vec_mul(&Exact, &powers[0], &age_to_count)))
 }
 
 fn read_input() -> Result<Vec<u8>> {
@@ -67,3 +551,121 @@ fn read_input() -> Result<Vec<u8>> {
 
     Ok(nums)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        count_after_many_days, days_until, simulate, timeline, Config, Exact, LifeCycle, Modulo,
+    };
+    use num_bigint::BigUint;
+
+    fn count_after(fishies: &[u8], life_cycle: &LifeCycle, days: u32) -> Result<u64, String> {
+        let timeline = timeline(fishies, life_cycle, days).map_err(|err| err.to_string())?;
+        Ok(timeline.last().unwrap().1)
+    }
+
+    #[test]
+    fn simulation_and_counts_agree() {
+        let fishies = [3, 4, 3, 1, 2];
+        let default = LifeCycle::default();
+        assert_eq!(simulate(&fishies, &default, 80).len(), 5934);
+        assert_eq!(count_after(&fishies, &default, 80).unwrap(), 5934);
+        assert_eq!(count_after(&fishies, &default, 256).unwrap(), 26984457539);
+
+        let life_cycle = LifeCycle {
+            reset: 2,
+            newborn: 4,
+            offspring: 3,
+        };
+        for days in 0..12 {
+            assert_eq!(
+                simulate(&fishies, &life_cycle, days).len() as u64,
+                count_after(&fishies, &life_cycle, days).unwrap()
+            );
+        }
+        assert!(count_after(&fishies, &life_cycle, 1000).is_err());
+    }
+
+    #[test]
+    fn matrix_power() {
+        let fishies = [3, 4, 3, 1, 2];
+        let default = LifeCycle::default();
+        let exact = count_after_many_days(&Exact, &fishies, &default, 256);
+        assert_eq!(exact, BigUint::from(26984457539u64));
+        let life_cycle = LifeCycle {
+            reset: 2,
+            newborn: 4,
+            offspring: 3,
+        };
+        for days in 0..40 {
+            assert_eq!(
+                count_after_many_days(&Modulo(u64::MAX), &fishies, &life_cycle, days),
+                count_after(&fishies, &life_cycle, days as u32).unwrap()
+            );
+        }
+
+        let prime = 1_000_000_007;
+        let exact = count_after_many_days(&Exact, &fishies, &default, 5000);
+        assert_eq!(
+            BigUint::from(count_after_many_days(
+                &Modulo(prime),
+                &fishies,
+                &default,
+                5000
+            )),
+            exact % prime
+        );
+        // about sixty squarings of the matrix
+        let days = 1_000_000_000_000_000_000;
+        assert!(count_after_many_days(&Modulo(prime), &fishies, &default, days) < prime);
+    }
+
+    #[test]
+    fn timeline_of_example() {
+        let timeline = timeline(&[3, 4, 3, 1, 2], &LifeCycle::default(), 18).unwrap();
+        assert_eq!(timeline.len(), 19);
+        assert_eq!(timeline[0], (vec![0, 1, 1, 2, 1, 0, 0, 0, 0], 5));
+        assert_eq!(timeline[3], (vec![2, 1, 0, 0, 0, 1, 1, 1, 1], 7));
+        assert_eq!(timeline[18].1, 26);
+    }
+
+    #[test]
+    fn days_until_target() {
+        let fishies = [3, 4, 3, 1, 2];
+        let life_cycle = LifeCycle::default();
+        let timeline = timeline(&fishies, &life_cycle, 256).unwrap();
+        for target in [0u64, 5, 6, 26, 5934, 5935, 26984457539] {
+            let (day, age_to_count) =
+                days_until(&fishies, &life_cycle, &BigUint::from(target)).unwrap();
+            let first = timeline
+                .iter()
+                .position(|&(_, total)| total >= target)
+                .unwrap();
+            assert_eq!(day, first as u64);
+            let expected = timeline[first].0.iter().map(|&n| BigUint::from(n));
+            assert!(age_to_count.into_iter().eq(expected));
+        }
+
+        let target = BigUint::from(10u8).pow(1000);
+        let (day, _) = days_until(&fishies, &life_cycle, &target).unwrap();
+        assert!(count_after_many_days(&Exact, &fishies, &life_cycle, day - 1) < target);
+        assert!(count_after_many_days(&Exact, &fishies, &life_cycle, day) >= target);
+
+        let barren = LifeCycle {
+            offspring: 0,
+            ..life_cycle
+        };
+        assert!(days_until(&fishies, &barren, &BigUint::from(6u8)).is_none());
+    }
+
+    #[test]
+    fn config_arguments() {
+        let mut config = Config::default();
+        config.apply_arg("reset=4").unwrap();
+        config.apply_arg("part2-days=18").unwrap();
+        assert_eq!(config.life_cycle.reset, 4);
+        assert_eq!(config.part_2_days, 18);
+        assert!(config.apply_arg("lifespan=3").is_err());
+        assert!(config.apply_arg("offspring").is_err());
+    }
+}
@@ -1,49 +1,1103 @@
-use std::io;
+use std::io::{self, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Without an argument both puzzle parts are run, part one with the linear cost and
+/// part two with the triangular cost. A `cost=<model>` argument runs a single cost
+/// model instead, one of `linear`, `triangular`, `quadratic` or `table:<file>`, where
+/// the file lists the fuel for moving 0, 1, 2, ... steps, separated by commas or
+/// newlines. A crab can be given a weight, counting it that many times, by writing
+/// its position as `<position>*<weight>`. Every position with the least fuel is
+/// reported, and a `check` argument confirms the answer by trying every position,
+/// e.g.
+///   cargo run --bin day_07 < input.txt cost=table:costs.txt check
+///
+/// The total fuel at every position between the leftmost and rightmost crab can be
+/// written to a CSV file by `curve=<file>`, with a column for each cost model, and
+/// plotted with the least fuel marked by `plot=ascii` or `plot=<file>.svg`, e.g.
+///   cargo run --bin day_07 < input.txt curve=fuel.csv plot=fuel.svg
+///
+/// With `k=<n>` the crabs may instead gather at up to `n` positions, each crab going
+/// to the one that costs it least, e.g.
+///   cargo run --bin day_07 < input.txt k=3
+///
+/// Crabs on a plane are read instead with `metric=manhattan` or `metric=euclidean`,
+/// as `x,y` pairs, or `x,y*<weight>`, separated by spaces or newlines. Each crab
+/// uses fuel equal to its distance under that metric, e.g.
+///   cargo run --bin day_07 < crabs.txt metric=euclidean
 fn main() -> Result<()> {
-    let mut input = read_input()?;
-    input.sort();
-    let median = input[input.len() / 2];
-    let mean = input.iter().sum::<i32>() / input.len() as i32;
-    println!("median = {}", median);
-    println!("mean = {}", mean);
-    let mut current = mean;
-    let mut d = dist(current, &input);
-    let step = if dist(current + 1, &input) < d { 1 } else { -1 };
-    loop {
-        println!("current = {}, d = {}", current, d);
-        let cand = dist(current + step, &input);
-        if cand < d {
-            d = cand;
-            current += step;
+    let mut models = vec![
+        ("part 1", CostModel::Linear),
+        ("part 2", CostModel::Triangular),
+    ];
+    let mut check = false;
+    let mut curve_path = None;
+    let mut plot = None;
+    let mut metric = None;
+    let mut clusters = None;
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("cost", model)) => models = vec![("cost model", CostModel::parse(model)?)],
+            Some(("curve", path)) => curve_path = Some(path.to_string()),
+            Some(("plot", "ascii")) => plot = Some("ascii".to_string()),
+            Some(("plot", path)) if path.ends_with(".svg") => plot = Some(path.to_string()),
+            Some(("metric", "manhattan")) => metric = Some(Metric::Manhattan),
+            Some(("metric", "euclidean")) => metric = Some(Metric::Euclidean),
+            Some(("k", k)) => clusters = Some(k.parse()?),
+            None if arg == "check" => check = true,
+            _ => Err(format!("unexpected argument {:?}", arg))?,
+        }
+    }
+    if let Some(metric) = metric {
+        return part_planar(&read_planar_input()?, metric);
+    }
+    let mut crabs = read_input()?;
+    crabs.sort_by_key(|crab| crab.position);
+    let mut curves = vec![];
+    for (label, model) in &models {
+        println!("{} ({})", label, model.name());
+        let optimum = align(&crabs, model)?;
+        println!(
+            "minimum fuel {} at {}",
+            optimum.fuel,
+            format_positions(&optimum.positions)
+        );
+        if check {
+            if scan(&crabs, model)? != optimum {
+                Err("scanning every position finds a different minimum")?
+            }
+            println!("confirmed by scanning every position");
+        }
+        if curve_path.is_some() || plot.is_some() {
+            let curve = cost_curve(&crabs, model)?;
+            if plot.as_deref() == Some("ascii") {
+                print!("{}", ascii_plot(crabs[0].position, &curve, &optimum));
+            }
+            curves.push((model.name(), curve, optimum));
+        }
+        if let Some(k) = clusters {
+            let clusters = k_medians(&crabs, model, k)?;
+            println!(
+                "with {} positions, minimum fuel {}",
+                clusters.len(),
+                clusters.iter().map(|cluster| cluster.fuel).sum::<u64>()
+            );
+            for cluster in clusters {
+                println!(
+                    "- at {}: {} crabs from {} to {}, fuel {}",
+                    cluster.position, cluster.weight, cluster.from, cluster.to, cluster.fuel
+                );
+            }
+        }
+    }
+    let lo = crabs[0].position;
+    if let Some(path) = curve_path {
+        write_curves_csv(lo, &curves, &path)?;
+    }
+    if let Some(path) = plot.filter(|plot| plot != "ascii") {
+        std::fs::write(path, svg_plot(lo, &curves))?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Crab {
+    position: i64,
+    weight: u64,
+}
+
+/// how much fuel a crab uses to move a distance
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum CostModel {
+    /// one unit per step
+    Linear,
+    /// one more unit for each step than for the step before, `d (d + 1) / 2`
+    Triangular,
+    /// the square of the distance
+    Quadratic,
+    /// the cost of each distance, from 0 upwards
+    Table(Vec<u64>),
+}
+
+impl CostModel {
+    fn parse(model: &str) -> Result<Self> {
+        Ok(match model {
+            "linear" => CostModel::Linear,
+            "triangular" => CostModel::Triangular,
+            "quadratic" => CostModel::Quadratic,
+            model => match model.strip_prefix("table:") {
+                Some(path) => CostModel::Table(read_table(path)?),
+                None => Err(format!("unknown cost model {:?}", model))?,
+            },
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CostModel::Linear => "linear",
+            CostModel::Triangular => "triangular",
+            CostModel::Quadratic => "quadratic",
+            CostModel::Table(_) => "table",
+        }
+    }
+
+    /// Whether the fuel for a signed distance is convex, so that the total fuel is
+    /// convex in the alignment position. A table must be convex and not fall at its
+    /// start.
+    fn is_convex(&self) -> bool {
+        match self {
+            CostModel::Linear | CostModel::Triangular | CostModel::Quadratic => true,
+            CostModel::Table(costs) => {
+                let steps = costs
+                    .windows(2)
+                    .map(|pair| pair[1] as i128 - pair[0] as i128)
+                    .collect::<Vec<_>>();
+                steps.first().is_none_or(|&step| step >= 0)
+                    && steps.windows(2).all(|pair| pair[0] <= pair[1])
+            }
+        }
+    }
+
+    /// The cost as `(a d + b d²) / 2` for a distance `d`, for the models that are
+    /// polynomials. The coefficients are doubled so that they are whole numbers for
+    /// the triangular cost, `(d + d²) / 2`.
+    fn polynomial(&self) -> Option<(i128, i128)> {
+        match self {
+            CostModel::Linear => Some((2, 0)),
+            CostModel::Triangular => Some((1, 1)),
+            CostModel::Quadratic => Some((0, 2)),
+            CostModel::Table(_) => None,
+        }
+    }
+
+    /// the fuel to move a distance, or `None` if it overflows or is beyond the end of
+    /// a table
+    fn cost(&self, distance: u64) -> Option<u64> {
+        match self {
+            CostModel::Linear => Some(distance),
+            CostModel::Triangular => distance
+                .checked_mul(distance + 1)
+                .map(|doubled| doubled / 2),
+            CostModel::Quadratic => distance.checked_mul(distance),
+            CostModel::Table(costs) => costs.get(usize::try_from(distance).ok()?).copied(),
+        }
+    }
+}
+
+fn read_table(path: &str) -> Result<Vec<u64>> {
+    let costs = std::fs::read_to_string(path)?
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse())
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if costs.is_empty() {
+        Err(format!("no costs in {}", path))?
+    }
+    Ok(costs)
+}
+
+/// the fuel for every crab to move to a position, or `None` if it can't be worked out
+fn total_fuel(crabs: &[Crab], model: &CostModel, to: i64) -> Option<u64> {
+    crabs.iter().try_fold(0u64, |total, crab| {
+        let fuel = model.cost(crab.position.abs_diff(to))?;
+        total.checked_add(fuel.checked_mul(crab.weight)?)
+    })
+}
+
+/// the position where crabs sorted by position reach half of the total weight
+fn weighted_median(crabs: &[Crab]) -> i64 {
+    let total = crabs.iter().map(|crab| crab.weight as u128).sum::<u128>();
+    let mut seen = 0;
+    for crab in crabs {
+        seen += crab.weight as u128;
+        if 2 * seen >= total {
+            return crab.position;
+        }
+    }
+    crabs.last().map_or(0, |crab| crab.position)
+}
+
+/// the weighted mean of the positions, as a numerator and a positive denominator
+fn weighted_mean(crabs: &[Crab]) -> (i128, i128) {
+    let total = crabs.iter().map(|crab| crab.weight as i128).sum::<i128>();
+    let sum = crabs
+        .iter()
+        .map(|crab| crab.position as i128 * crab.weight as i128)
+        .sum::<i128>();
+    (sum, total.max(1))
+}
+
+/// every position with the least fuel, as inclusive ranges
+#[derive(Debug, PartialEq, Eq)]
+struct Optimum {
+    fuel: u64,
+    positions: Vec<(i64, i64)>,
+}
+
+/// the fuel to reach a position, as an error if it can't be worked out
+fn fuel_at(crabs: &[Crab], model: &CostModel, position: i64) -> Result<u64> {
+    Ok(total_fuel(crabs, model, position)
+        .ok_or(format!("can't work out the fuel to reach {}", position))?)
+}
+
+/// Where the crabs should line up and the fuel it takes, looking only between the
+/// leftmost and rightmost crab. When the cost is convex in the distance, so is the
+/// total fuel, and the positions with the least fuel form a single range:
+/// - the linear cost is least at the weighted median
+/// - the triangular cost is least within half a step of the weighted mean, so at
+///   the floor or ceiling of the mean or right next to them
+/// - other convex costs are searched for by a ternary search
+///
+/// Starting from one such position, the ends of the range are found by binary
+/// search, as the fuel only rises moving away from it. Costs that aren't convex are
+/// scanned over every position.
+fn align(crabs: &[Crab], model: &CostModel) -> Result<Optimum> {
+    let (lo, hi) = (crabs[0].position, crabs[crabs.len() - 1].position);
+    let best = match model {
+        CostModel::Linear => weighted_median(crabs),
+        CostModel::Triangular => {
+            let (sum, total) = weighted_mean(crabs);
+            let floor = sum.div_euclid(total) as i64;
+            let ceil = floor + (sum.rem_euclid(total) != 0) as i64;
+            let mut best = floor.clamp(lo, hi);
+            for position in floor.saturating_sub(1).max(lo)..=ceil.saturating_add(1).min(hi) {
+                if fuel_at(crabs, model, position)? < fuel_at(crabs, model, best)? {
+                    best = position;
+                }
+            }
+            best
+        }
+        model if model.is_convex() => ternary_search(crabs, model, lo, hi)?,
+        _ => return scan(crabs, model),
+    };
+    let fuel = fuel_at(crabs, model, best)?;
+    // the fuel is `fuel` on a range around `best` and higher outside it
+    let first = partition_point(lo.into(), best.into(), |position| {
+        Ok(fuel_at(crabs, model, position)? > fuel)
+    })? as i64;
+    let end = partition_point(best.into(), hi as i128 + 1, |position| {
+        Ok(fuel_at(crabs, model, position)? == fuel)
+    })?;
+    let last = (end - 1) as i64;
+    Ok(Optimum {
+        fuel,
+        positions: vec![(first, last)],
+    })
+}
+
+/// The first position in `lo..hi` for which `pred` is false, where it is true for
+/// every position before that and false for every position after. The bounds are
+/// `i128` so that `hi` can be one past `i64::MAX`, and so that crabs at both ends
+/// of the `i64` range don't overflow the midpoint.
+fn partition_point(lo: i128, hi: i128, pred: impl Fn(i64) -> Result<bool>) -> Result<i128> {
+    let (mut lo, mut hi) = (lo, hi);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid as i64)? {
+            lo = mid + 1;
         } else {
-            break;
+            hi = mid;
         }
     }
-    println!("minimum is {} with dist {}", current, d);
+    Ok(lo)
+}
+
+/// The first position in `lo..=hi` with the least fuel, when the fuel is convex.
+/// Comparing the fuel at neighbouring positions tells which side of the minimum they
+/// are on, so each comparison halves the range.
+fn ternary_search(crabs: &[Crab], model: &CostModel, lo: i64, hi: i64) -> Result<i64> {
+    let best = partition_point(lo.into(), hi.into(), |position| {
+        Ok(fuel_at(crabs, model, position + 1)? < fuel_at(crabs, model, position)?)
+    })?;
+    Ok(best as i64)
+}
+
+/// finds the least fuel by trying every position between the leftmost and rightmost
+/// crab, which makes no assumptions about the cost
+fn scan(crabs: &[Crab], model: &CostModel) -> Result<Optimum> {
+    let lo = crabs[0].position;
+    let mut optimum = Optimum {
+        fuel: u64::MAX,
+        positions: vec![],
+    };
+    for (position, fuel) in (lo..).zip(cost_curve(crabs, model)?) {
+        if fuel < optimum.fuel {
+            optimum.fuel = fuel;
+            optimum.positions.clear();
+        }
+        if fuel == optimum.fuel {
+            match optimum.positions.last_mut() {
+                Some(last) if last.1 + 1 == position => last.1 = position,
+                _ => optimum.positions.push((position, position)),
+            }
+        }
+    }
+    Ok(optimum)
+}
+
+/// the most positions a cost curve may cover
+const MAX_CURVE_POSITIONS: u64 = 1 << 26;
+
+/// The total fuel at every position from the leftmost crab to the rightmost. For
+/// the polynomial costs, the fuel at `x` from the crabs at or left of it is
+/// `x W - S` for the linear part and `x² W - 2x S + Q` for the square, where `W`,
+/// `S` and `Q` are the sums of the weights, weighted positions and weighted squared
+/// positions of those crabs, and likewise from the right. Those sums are kept as
+/// prefix sums while sweeping `x` along, so this takes O(n + range). Tables are
+/// worked out at each position, in O(n range).
+fn cost_curve(crabs: &[Crab], model: &CostModel) -> Result<Vec<u64>> {
+    let (lo, hi) = (crabs[0].position, crabs[crabs.len() - 1].position);
+    if hi.abs_diff(lo) >= MAX_CURVE_POSITIONS {
+        Err(format!(
+            "crabs from {} to {} span more than {} positions",
+            lo, hi, MAX_CURVE_POSITIONS
+        ))?
+    }
+    let Some((linear, square)) = model.polynomial() else {
+        return (lo..=hi)
+            .map(|position| fuel_at(crabs, model, position))
+            .collect();
+    };
+    // sums of weight, weighted position and weighted squared position, for all
+    // crabs and for the crabs left of the sweep, measuring positions from `lo`
+    let sums = |crab: &Crab| {
+        let (w, p) = (crab.weight as i128, (crab.position - lo) as i128);
+        [w, w * p, w * p * p]
+    };
+    let add = |a: [i128; 3], b: [i128; 3]| [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+    let all = crabs.iter().map(sums).fold([0; 3], add);
+    let mut left = [0; 3];
+    let mut next_crab = crabs.iter().peekable();
+    let mut curve = Vec::with_capacity((hi - lo) as usize + 1);
+    for x in lo..=hi {
+        while let Some(crab) = next_crab.next_if(|crab| crab.position <= x) {
+            left = add(left, sums(crab));
+        }
+        let position = x;
+        let x = (x - lo) as i128;
+        let right = [all[0] - left[0], all[1] - left[1], all[2] - left[2]];
+        let distances = (x * left[0] - left[1]) + (right[1] - x * right[0]);
+        let squares = x * x * all[0] - 2 * x * all[1] + all[2];
+        let doubled = linear * distances + square * squares;
+        curve.push(
+            u64::try_from(doubled / 2)
+                .map_err(|_| format!("the fuel to reach {} overflows", position))?,
+        );
+    }
+    Ok(curve)
+}
+
+/// the furthest apart crabs may be, and the most total weight they may have, for
+/// k-medians, so that the sums it keeps can't overflow
+const MAX_CLUSTER_SPAN: u64 = 1 << 31;
+const MAX_CLUSTER_WEIGHT: u64 = 1 << 32;
+
+/// crabs that gather at one position, being every crab from `from` to `to`
+#[derive(Debug, PartialEq, Eq)]
+struct Cluster {
+    position: i64,
+    from: i64,
+    to: i64,
+    weight: u64,
+    fuel: u64,
+}
+
+/// Prefix sums of the weight, weighted position and weighted squared position of
+/// crabs sorted by position, measuring positions from the first crab, so that the
+/// fuel for any run of crabs to reach a position takes O(log n).
+struct PrefixSums {
+    lo: i64,
+    positions: Vec<i64>,
+    sums: Vec<[i128; 3]>,
+}
+
+impl PrefixSums {
+    fn new(crabs: &[Crab]) -> Self {
+        let lo = crabs[0].position;
+        let mut sums = vec![[0; 3]];
+        for crab in crabs {
+            let (w, p) = (crab.weight as i128, (crab.position - lo) as i128);
+            let last = sums[sums.len() - 1];
+            sums.push([last[0] + w, last[1] + w * p, last[2] + w * p * p]);
+        }
+        PrefixSums {
+            lo,
+            positions: crabs.iter().map(|crab| crab.position).collect(),
+            sums,
+        }
+    }
+
+    /// the sums over the crabs in `from..to`
+    fn between(&self, from: usize, to: usize) -> [i128; 3] {
+        let (a, b) = (self.sums[from], self.sums[to]);
+        [b[0] - a[0], b[1] - a[1], b[2] - a[2]]
+    }
+
+    /// the fuel for the crabs in `from..to` to reach a position, as in `cost_curve`
+    fn fuel(&self, (linear, square): (i128, i128), from: usize, to: usize, position: i64) -> i128 {
+        let split = from + self.positions[from..to].partition_point(|&p| p <= position);
+        let (left, right, all) = (
+            self.between(from, split),
+            self.between(split, to),
+            self.between(from, to),
+        );
+        let x = (position - self.lo) as i128;
+        let distances = (x * left[0] - left[1]) + (right[1] - x * right[0]);
+        let squares = x * x * all[0] - 2 * x * all[1] + all[2];
+        (linear * distances + square * squares) / 2
+    }
+
+    /// The best position for the crabs in `from..to` and its fuel. The linear cost
+    /// is least at their weighted median, and the others within a step of their
+    /// weighted mean.
+    fn optimum(&self, polynomial: (i128, i128), from: usize, to: usize) -> (i64, i128) {
+        let [weight, sum, _] = self.between(from, to);
+        let (first, last) = (self.positions[from], self.positions[to - 1]);
+        if weight == 0 {
+            return (first, 0);
+        }
+        let candidates = if polynomial.1 == 0 {
+            // the first crab that brings the weight so far to at least half
+            let idx = self.sums[from + 1..=to]
+                .partition_point(|sums| 2 * (sums[0] - self.sums[from][0]) < weight);
+            let median = self.positions[from + idx];
+            median..=median
+        } else {
+            let floor = self.lo + sum.div_euclid(weight) as i64;
+            (floor - 1).clamp(first, last)..=(floor + 2).clamp(first, last)
+        };
+        candidates
+            .map(|position| (position, self.fuel(polynomial, from, to, position)))
+            .min_by_key(|&(_, fuel)| fuel)
+            .unwrap()
+    }
+}
+
+/// Gathers crabs sorted by position at `k` positions with the least total fuel,
+/// each crab going to the position that costs it least. That makes each gathering
+/// a run of neighbouring crabs, so dynamic programming over where each run ends
+/// finds the best split in O(k n² log n). Crabs at the same position always stay
+/// together, so there are at most as many gatherings as distinct positions.
+fn k_medians(crabs: &[Crab], model: &CostModel, k: usize) -> Result<Vec<Cluster>> {
+    let polynomial = model
+        .polynomial()
+        .ok_or("gathering at several positions needs a linear, triangular or quadratic cost")?;
+    if k == 0 {
+        Err("k must be at least 1")?
+    }
+    let (lo, hi) = (crabs[0].position, crabs[crabs.len() - 1].position);
+    let total_weight = crabs.iter().map(|crab| crab.weight as u128).sum::<u128>();
+    if hi.abs_diff(lo) > MAX_CLUSTER_SPAN || total_weight > MAX_CLUSTER_WEIGHT as u128 {
+        Err("crabs are too far apart or too heavy to gather at several positions")?
+    }
+    let mut merged: Vec<Crab> = vec![];
+    for &crab in crabs {
+        match merged.last_mut() {
+            Some(last) if last.position == crab.position => last.weight += crab.weight,
+            _ => merged.push(crab),
+        }
+    }
+    let n = merged.len();
+    let k = k.min(n);
+    let prefix = PrefixSums::new(&merged);
+    // best[c][j] is the least fuel for the first j positions to gather in c runs,
+    // and where the last of those runs starts
+    let mut best = vec![vec![(i128::MAX, 0); n + 1]; k + 1];
+    best[0][0] = (0, 0);
+    for c in 1..=k {
+        for j in c..=n {
+            for i in c - 1..j {
+                let (before, _) = best[c - 1][i];
+                if before == i128::MAX {
+                    continue;
+                }
+                let fuel = before + prefix.optimum(polynomial, i, j).1;
+                if fuel < best[c][j].0 {
+                    best[c][j] = (fuel, i);
+                }
+            }
+        }
+    }
+    let mut clusters = vec![];
+    let mut end = n;
+    for c in (1..=k).rev() {
+        let start = best[c][end].1;
+        let (position, fuel) = prefix.optimum(polynomial, start, end);
+        clusters.push(Cluster {
+            position,
+            from: merged[start].position,
+            to: merged[end - 1].position,
+            weight: merged[start..end].iter().map(|crab| crab.weight).sum(),
+            fuel: u64::try_from(fuel)?,
+        });
+        end = start;
+    }
+    clusters.reverse();
+    Ok(clusters)
+}
+
+fn write_curves_csv(lo: i64, curves: &[(&str, Vec<u64>, Optimum)], path: &str) -> Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write!(file, "position")?;
+    for (name, _, _) in curves {
+        write!(file, ",{}", name)?;
+    }
+    writeln!(file)?;
+    let len = curves.first().map_or(0, |(_, curve, _)| curve.len());
+    for idx in 0..len {
+        write!(file, "{}", lo + idx as i64)?;
+        for (_, curve, _) in curves {
+            write!(file, ",{}", curve[idx])?;
+        }
+        writeln!(file)?;
+    }
     Ok(())
 }
 
-fn dist(from: i32, positions: &Vec<i32>) -> i32 {
+/// the most rows and the widest bar in the text plot
+const ASCII_PLOT_ROWS: usize = 60;
+const ASCII_PLOT_WIDTH: usize = 60;
+
+/// How far each fuel is above the least fuel, as a fraction of the range of fuels,
+/// so that curves of different sizes can be compared by shape.
+fn above_minimum(curve: &[u64], optimum: &Optimum) -> Vec<f64> {
+    let max = curve.iter().copied().max().unwrap_or(0);
+    let spread = (max - optimum.fuel).max(1) as f64;
+    curve
+        .iter()
+        .map(|&fuel| (fuel - optimum.fuel) as f64 / spread)
+        .collect()
+}
+
+/// one row per position, or per few positions for wide ranges, with a bar for how
+/// far the fuel is above the least, and the positions with the least fuel marked
+fn ascii_plot(lo: i64, curve: &[u64], optimum: &Optimum) -> String {
+    let heights = above_minimum(curve, optimum);
+    let every = curve.len().div_ceil(ASCII_PLOT_ROWS).max(1);
+    let is_optimal = |idx: usize| {
+        let position = lo + idx as i64;
+        optimum
+            .positions
+            .iter()
+            .any(|&(first, last)| (first..=last).contains(&position))
+    };
+    let mut rows = (0..curve.len()).step_by(every).collect::<Vec<_>>();
+    rows.extend(
+        (0..curve.len())
+            .filter(|&idx| is_optimal(idx))
+            .take(ASCII_PLOT_ROWS),
+    );
+    rows.sort_unstable();
+    rows.dedup();
+    let mut plot = String::new();
+    for idx in rows {
+        let bar = (heights[idx] * ASCII_PLOT_WIDTH as f64).round() as usize;
+        plot += &format!(
+            "{:>8} |{:<width$}| {}{}\n",
+            lo + idx as i64,
+            "#".repeat(bar),
+            curve[idx],
+            if is_optimal(idx) { " <- minimum" } else { "" },
+            width = ASCII_PLOT_WIDTH
+        );
+    }
+    plot
+}
+
+/// each curve as a line scaled between its least and greatest fuel, with a dot at
+/// the positions with the least fuel
+fn svg_plot(lo: i64, curves: &[(&str, Vec<u64>, Optimum)]) -> String {
+    let (width, height, margin) = (800.0, 400.0, 50.0);
+    let colours = ["steelblue", "darkorange", "seagreen", "purple"];
+    let len = curves.first().map_or(1, |(_, curve, _)| curve.len());
+    let x = |idx: f64| margin + idx / (len - 1).max(1) as f64 * (width - 2.0 * margin);
+    let y = |height_above: f64| height - margin - height_above * (height - 2.0 * margin);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        width, height
+    );
+    for (n, (name, curve, optimum)) in curves.iter().enumerate() {
+        let colour = colours[n % colours.len()];
+        let points = above_minimum(curve, optimum)
+            .iter()
+            .enumerate()
+            .map(|(idx, &h)| format!("{:.1},{:.1}", x(idx as f64), y(h)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        svg += &format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+            points, colour
+        );
+        for &(first, last) in &optimum.positions {
+            let (from, to) = (x((first - lo) as f64), x((last - lo) as f64));
+            svg += &format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" \
+                 stroke=\"red\" stroke-width=\"4\" stroke-linecap=\"round\"/>\n",
+                from,
+                y(0.0),
+                to,
+                y(0.0)
+            );
+        }
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" fill=\"{}\">\
+             {}: least fuel {} at {}</text>\n",
+            margin,
+            margin / 2.0 + 14.0 * n as f64,
+            colour,
+            name,
+            optimum.fuel,
+            format_positions(&optimum.positions)
+        );
+    }
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"middle\">\
+         position {} to {}</text>\n</svg>\n",
+        width / 2.0,
+        height - margin / 3.0,
+        lo,
+        lo + len as i64 - 1
+    );
+    svg
+}
+
+fn format_positions(positions: &[(i64, i64)]) -> String {
     positions
         .iter()
-        .map(|&pos| {
-            let n = (from - pos).abs();
-            (n * (n + 1)) / 2
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}..={}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_crab(crab: &str) -> Result<Crab> {
+    Ok(match crab.split_once('*') {
+        Some((position, weight)) => Crab {
+            position: position.trim().parse()?,
+            weight: weight.trim().parse()?,
+        },
+        None => Crab {
+            position: crab.trim().parse()?,
+            weight: 1,
+        },
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PlanarCrab {
+    position: (i64, i64),
+    weight: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Metric {
+    Manhattan,
+    Euclidean,
+}
+
+fn part_planar(crabs: &[PlanarCrab], metric: Metric) -> Result<()> {
+    println!("{:?} distance", metric);
+    match metric {
+        Metric::Manhattan => {
+            let (x, y) = manhattan_optimum(crabs)?;
+            println!(
+                "minimum fuel {} at x in {} and y in {}",
+                x.fuel + y.fuel,
+                format_positions(&x.positions),
+                format_positions(&y.positions)
+            );
+        }
+        Metric::Euclidean => {
+            let (x, y) = geometric_median(crabs);
+            println!("geometric median ({:.6}, {:.6})", x, y);
+            let (point, fuel) = nearest_integer_optimum(crabs, (x, y));
+            println!("minimum fuel {:.6} at whole-number point {:?}", fuel, point);
+        }
+    }
+    Ok(())
+}
+
+/// The Manhattan distance is the sum of the distances along each axis, so the least
+/// fuel is at the weighted median on each axis independently, and the best points
+/// are every combination of the best `x` and the best `y`.
+fn manhattan_optimum(crabs: &[PlanarCrab]) -> Result<(Optimum, Optimum)> {
+    let axis = |coordinate: fn((i64, i64)) -> i64| {
+        let mut projected = crabs
+            .iter()
+            .map(|crab| Crab {
+                position: coordinate(crab.position),
+                weight: crab.weight,
+            })
+            .collect::<Vec<_>>();
+        projected.sort_by_key(|crab| crab.position);
+        align(&projected, &CostModel::Linear)
+    };
+    Ok((axis(|(x, _)| x)?, axis(|(_, y)| y)?))
+}
+
+fn euclidean_fuel(crabs: &[PlanarCrab], (x, y): (f64, f64)) -> f64 {
+    crabs
+        .iter()
+        .map(|crab| {
+            let (dx, dy) = (crab.position.0 as f64 - x, crab.position.1 as f64 - y);
+            crab.weight as f64 * dx.hypot(dy)
         })
         .sum()
 }
 
-fn read_input() -> Result<Vec<i32>> {
+/// the most Weiszfeld steps taken, and the movement at which they stop early
+const WEISZFELD_ITERATIONS: usize = 10_000;
+const WEISZFELD_TOLERANCE: f64 = 1e-9;
+
+/// the ternary search steps along a column, each narrowing it by a third, and how
+/// much worse than the best point a column minimum must be to stop the search
+const COLUMN_SEARCH_STEPS: usize = 200;
+const FUEL_TOLERANCE: f64 = 1e-9;
+
+/// The point with the least total Euclidean distance to the crabs, found by
+/// Weiszfeld's iteration from the weighted mean: each step moves to the mean of the
+/// crabs weighted by their weight over their distance. A crab that the point lands
+/// on is left out of that mean, and the point stays on it when the pull of the
+/// other crabs is no more than that crab's weight.
+fn geometric_median(crabs: &[PlanarCrab]) -> (f64, f64) {
+    let total = crabs.iter().map(|crab| crab.weight as f64).sum::<f64>();
+    if total == 0.0 {
+        return crabs.first().map_or((0.0, 0.0), |crab| {
+            (crab.position.0 as f64, crab.position.1 as f64)
+        });
+    }
+    let mut point = crabs.iter().fold((0.0, 0.0), |(x, y), crab| {
+        let w = crab.weight as f64 / total;
+        (
+            x + w * crab.position.0 as f64,
+            y + w * crab.position.1 as f64,
+        )
+    });
+    for _ in 0..WEISZFELD_ITERATIONS {
+        let (mut sum_x, mut sum_y, mut sum_w) = (0.0, 0.0, 0.0);
+        let (mut pull_x, mut pull_y, mut weight_here) = (0.0, 0.0, 0.0);
+        for crab in crabs {
+            let (cx, cy) = (crab.position.0 as f64, crab.position.1 as f64);
+            let distance = (cx - point.0).hypot(cy - point.1);
+            let weight = crab.weight as f64;
+            if distance < WEISZFELD_TOLERANCE {
+                weight_here += weight;
+                continue;
+            }
+            sum_x += weight * cx / distance;
+            sum_y += weight * cy / distance;
+            sum_w += weight / distance;
+            pull_x += weight * (cx - point.0) / distance;
+            pull_y += weight * (cy - point.1) / distance;
+        }
+        if sum_w == 0.0 || (weight_here > 0.0 && pull_x.hypot(pull_y) <= weight_here) {
+            break;
+        }
+        let next = (sum_x / sum_w, sum_y / sum_w);
+        let moved = (next.0 - point.0).hypot(next.1 - point.1);
+        point = next;
+        if moved < WEISZFELD_TOLERANCE {
+            break;
+        }
+    }
+    point
+}
+
+/// The whole-number point with the least total Euclidean distance. The total is
+/// convex, so for a fixed `x` the best `y` is at the floor or ceiling of the real
+/// minimum along that column, and the column minimums are convex in `x`. Columns are
+/// tried outwards from the geometric median until their minimum is worse than the
+/// best whole-number point so far, which can then no longer be beaten.
+fn nearest_integer_optimum(crabs: &[PlanarCrab], (x, _): (f64, f64)) -> ((i64, i64), f64) {
+    let fuel = |(x, y): (i64, i64)| euclidean_fuel(crabs, (x as f64, y as f64));
+    let min_max = |coordinate: fn(&PlanarCrab) -> i64| {
+        let values = crabs.iter().map(coordinate);
+        (values.clone().min().unwrap(), values.max().unwrap())
+    };
+    // no point outside the crabs' bounding box is better than its nearest point inside
+    let (min_x, max_x) = min_max(|crab| crab.position.0);
+    let (min_y, max_y) = min_max(|crab| crab.position.1);
+    let column_minimum = |x: i64| {
+        let (mut lo, mut hi) = (min_y as f64, max_y as f64);
+        for _ in 0..COLUMN_SEARCH_STEPS {
+            let (a, b) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+            if euclidean_fuel(crabs, (x as f64, a)) <= euclidean_fuel(crabs, (x as f64, b)) {
+                hi = b;
+            } else {
+                lo = a;
+            }
+        }
+        let y = (lo + hi) / 2.0;
+        (y, euclidean_fuel(crabs, (x as f64, y)))
+    };
+    let mut best: Option<((i64, i64), f64)> = None;
+    // tries a column, giving false once it can't hold a better point
+    let mut try_column = |x: i64| {
+        let (y, least) = column_minimum(x);
+        if best.is_some_and(|(_, best_fuel)| least > best_fuel + FUEL_TOLERANCE) {
+            return false;
+        }
+        for y in [y.floor() as i64, y.ceil() as i64] {
+            let point = (x, y.clamp(min_y, max_y));
+            let candidate = fuel(point);
+            if best.is_none_or(|(_, best_fuel)| candidate < best_fuel) {
+                best = Some((point, candidate));
+            }
+        }
+        true
+    };
+    let start = (x.floor() as i64).clamp(min_x, max_x);
+    for x in start..=max_x {
+        if !try_column(x) {
+            break;
+        }
+    }
+    for x in (min_x..start).rev() {
+        if !try_column(x) {
+            break;
+        }
+    }
+    best.expect("there is at least one crab")
+}
+
+fn parse_planar_crab(crab: &str) -> Result<PlanarCrab> {
+    let (position, weight) = match crab.split_once('*') {
+        Some((position, weight)) => (position, weight.parse()?),
+        None => (crab, 1),
+    };
+    let (x, y) = position
+        .split_once(',')
+        .ok_or(format!("expected x,y, not {:?}", crab))?;
+    Ok(PlanarCrab {
+        position: (x.parse()?, y.parse()?),
+        weight,
+    })
+}
+
+fn read_planar_input() -> Result<Vec<PlanarCrab>> {
+    let input = io::read_to_string(io::stdin())?;
+    let crabs = input
+        .split_whitespace()
+        .map(parse_planar_crab)
+        .collect::<Result<Vec<_>>>()?;
+    if crabs.is_empty() {
+        Err("no crabs")?
+    }
+    Ok(crabs)
+}
+
+fn read_input() -> Result<Vec<Crab>> {
     let stdin = io::stdin();
     let mut first_line = String::new();
     stdin.read_line(&mut first_line)?;
-    let nums = first_line
+    let crabs = first_line
         .trim()
         .split(",")
-        .map(|s| s.parse())
-        .collect::<std::result::Result<_, _>>()?;
-    Ok(nums)
+        .map(parse_crab)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(crabs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        align, cost_curve, euclidean_fuel, fuel_at, geometric_median, k_medians, manhattan_optimum,
+        nearest_integer_optimum, parse_crab, parse_planar_crab, scan, Cluster, CostModel, Crab,
+        Optimum, PlanarCrab,
+    };
+
+    fn example() -> Vec<Crab> {
+        let mut crabs = "16,1,2,0,4,2,7,1,2,14"
+            .split(',')
+            .map(|crab| parse_crab(crab).unwrap())
+            .collect::<Vec<_>>();
+        crabs.sort_by_key(|crab| crab.position);
+        crabs
+    }
+
+    #[test]
+    fn both_parts() {
+        let crabs = example();
+        let optimum = |fuel, position| Optimum {
+            fuel,
+            positions: vec![(position, position)],
+        };
+        assert_eq!(align(&crabs, &CostModel::Linear).unwrap(), optimum(37, 2));
+        assert_eq!(
+            align(&crabs, &CostModel::Triangular).unwrap(),
+            optimum(168, 5)
+        );
+        let table = CostModel::Table((0..=16).collect());
+        assert_eq!(align(&crabs, &table).unwrap(), optimum(37, 2));
+        let short_table = CostModel::Table(vec![0, 1, 2]);
+        assert!(align(&crabs, &short_table).is_err());
+    }
+
+    #[test]
+    fn weights() {
+        let crabs = [parse_crab("0*3").unwrap(), parse_crab("10").unwrap()];
+        assert_eq!(align(&crabs, &CostModel::Linear).unwrap().fuel, 10);
+        assert_eq!(
+            align(&crabs, &CostModel::Quadratic).unwrap(),
+            Optimum {
+                fuel: 3 * 4 + 64,
+                positions: vec![(2, 3)]
+            }
+        );
+        // the total weight is more than a u64 holds
+        let crabs = [
+            parse_crab("0*18446744073709551615").unwrap(),
+            parse_crab("1*5").unwrap(),
+        ];
+        assert_eq!(
+            align(&crabs, &CostModel::Linear).unwrap(),
+            Optimum {
+                fuel: 5,
+                positions: vec![(0, 0)]
+            }
+        );
+    }
+
+    #[test]
+    fn cost_curves() {
+        let mut crabs = example();
+        crabs.push(parse_crab("-3*4").unwrap());
+        crabs.sort_by_key(|crab| crab.position);
+        for model in [
+            CostModel::Linear,
+            CostModel::Triangular,
+            CostModel::Quadratic,
+            CostModel::Table((0..20).map(|d| d * d * d).collect()),
+        ] {
+            let expected = (-3..=16)
+                .map(|position| fuel_at(&crabs, &model, position).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(cost_curve(&crabs, &model).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn ties_and_scans() {
+        let crabs = [parse_crab("0").unwrap(), parse_crab("10").unwrap()];
+        let models = [
+            CostModel::Linear,
+            CostModel::Triangular,
+            CostModel::Quadratic,
+            CostModel::Table(vec![0, 4, 4, 9, 9, 9, 9, 9, 9, 9, 9]),
+        ];
+        for model in &models {
+            assert_eq!(align(&crabs, model).unwrap(), scan(&crabs, model).unwrap());
+        }
+        assert_eq!(
+            align(&crabs, &CostModel::Linear).unwrap().positions,
+            vec![(0, 10)]
+        );
+        assert_eq!(
+            align(&crabs, &models[3]).unwrap().positions,
+            vec![(0, 0), (10, 10)]
+        );
+    }
+
+    #[test]
+    fn crabs_at_the_ends_of_the_range() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        let crab = |position| Crab {
+            position,
+            weight: 1,
+        };
+        assert_eq!(
+            align(&[crab(0), crab(max)], &CostModel::Linear).unwrap(),
+            Optimum {
+                fuel: max as u64,
+                positions: vec![(0, max)]
+            }
+        );
+        assert_eq!(
+            align(&[crab(min), crab(max)], &CostModel::Linear).unwrap(),
+            Optimum {
+                fuel: u64::MAX,
+                positions: vec![(min, max)]
+            }
+        );
+        for model in [CostModel::Triangular, CostModel::Quadratic] {
+            for crabs in [[crab(max - 2), crab(max)], [crab(min), crab(min + 2)]] {
+                let middle = crabs[0].position + 1;
+                assert_eq!(
+                    align(&crabs, &model).unwrap(),
+                    Optimum {
+                        fuel: 2,
+                        positions: vec![(middle, middle)]
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gathering_at_several_positions() {
+        let crabs = example();
+        let cluster = |position, from, to, weight, fuel| Cluster {
+            position,
+            from,
+            to,
+            weight,
+            fuel,
+        };
+        assert_eq!(
+            k_medians(&crabs, &CostModel::Triangular, 2).unwrap(),
+            vec![cluster(2, 0, 7, 8, 23), cluster(15, 14, 16, 2, 2)]
+        );
+        let one = k_medians(&crabs, &CostModel::Linear, 1).unwrap();
+        assert_eq!(one, vec![cluster(2, 0, 16, 10, 37)]);
+        // there are only seven distinct positions
+        let all = k_medians(&crabs, &CostModel::Linear, 20).unwrap();
+        assert_eq!(all.len(), 7);
+        assert!(all.iter().all(|cluster| cluster.fuel == 0));
+        let table = CostModel::Table(vec![0, 1]);
+        assert!(k_medians(&crabs, &table, 2).is_err());
+    }
+
+    fn planar(crabs: &str) -> Vec<PlanarCrab> {
+        crabs
+            .split_whitespace()
+            .map(|crab| parse_planar_crab(crab).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn manhattan() {
+        let crabs = planar("0,0 4,1 1,5 3,3*2");
+        let (x, y) = manhattan_optimum(&crabs).unwrap();
+        assert_eq!(x.positions, vec![(3, 3)]);
+        assert_eq!(y.positions, vec![(3, 3)]);
+        assert_eq!(x.fuel + y.fuel, (3 + 1 + 2) + (3 + 2 + 2));
+    }
+
+    #[test]
+    fn euclidean() {
+        // the Fermat point of a triangle with no angle of 120° or more
+        let crabs = planar("0,0 10,0 5,9");
+        let (x, y) = geometric_median(&crabs);
+        assert!((x - 5.0).abs() < 1e-6);
+        assert!((y - 5.0 / 3f64.sqrt()).abs() < 1e-6);
+        let (point, fuel) = nearest_integer_optimum(&crabs, (x, y));
+        let best = (-5..15)
+            .flat_map(|x| (-5..15).map(move |y| (x, y)))
+            .min_by(|&a, &b| {
+                let fuel = |(x, y): (i64, i64)| euclidean_fuel(&crabs, (x as f64, y as f64));
+                fuel(a).total_cmp(&fuel(b))
+            })
+            .unwrap();
+        assert_eq!(point, best);
+        assert!(fuel >= euclidean_fuel(&crabs, (x, y)));
+
+        // a heavy enough crab is the median itself
+        let crabs = planar("0,0*5 10,0 0,10 -10,-10");
+        assert_eq!(geometric_median(&crabs), (0.0, 0.0));
+    }
 }
@@ -1,150 +1,307 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeSet, HashMap};
 use std::io::{self, BufRead};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// the usual digits on a 7-segment display, in the definition file format
+const SEVEN_SEGMENT: &str = "\
+0 abcefg
+1 cf
+2 acdeg
+3 acdfg
+4 bcdf
+5 abdfg
+6 abdefg
+7 acf
+8 abcdefg
+9 abcdfg
+";
+
+/// Each entry's wiring is worked out for a 7-segment display of the usual digits.
+/// A `display=<file>` argument reads a different display instead, such as a
+/// 14-segment display with letters, defined in the format that `Display::parse`
+/// describes, e.g.
+///   cargo run --bin day_08 < input.txt display=fourteen_segment.txt
+///
+/// Each output is printed as the glyphs it shows, and the outputs are totalled when
+/// every one of them is a number.
 fn main() -> Result<()> {
-    let digits_to_segments: BTreeMap<u8, BTreeSet<char>> = BTreeMap::from([
-        (0, BTreeSet::from(['a', 'b', 'c', 'e', 'f', 'g'])),
-        (1, BTreeSet::from(['c', 'f'])),
-        (2, BTreeSet::from(['a', 'c', 'd', 'e', 'g'])),
-        (3, BTreeSet::from(['a', 'c', 'd', 'f', 'g'])),
-        (4, BTreeSet::from(['b', 'c', 'd', 'f'])),
-        (5, BTreeSet::from(['a', 'b', 'd', 'f', 'g'])),
-        (6, BTreeSet::from(['a', 'b', 'd', 'e', 'f', 'g'])),
-        (7, BTreeSet::from(['a', 'c', 'f'])),
-        (8, BTreeSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-        (9, BTreeSet::from(['a', 'b', 'c', 'd', 'f', 'g'])),
-    ]);
-
-    let segments_to_digits: BTreeMap<BTreeSet<char>, u8> = digits_to_segments
-        .iter()
-        .map(|(digit, segments)| (segments.clone(), *digit))
-        .collect();
-
-    let all_entries = read_input()?;
-    let total: i64 = all_entries
-        .iter()
-        .map(|entry| solve_entry(&entry, &digits_to_segments, &segments_to_digits))
-        .sum();
-    println!("total = {}", total);
+    let mut display = Display::parse(SEVEN_SEGMENT)?;
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some(("display", path)) => display = Display::parse(&std::fs::read_to_string(path)?)?,
+            _ => Err(format!("unexpected argument {:?}", arg))?,
+        }
+    }
+
+    let all_entries = read_input(&display)?;
+    let mut total = Some(0i64);
+    let mut guessed = 0;
+    for entry in &all_entries {
+        let solution = display
+            .solve(&entry.signal_patterns)
+            .ok_or(format!("didn't manage to solve for entry {:?}", entry))?;
+        guessed += solution.guessed as usize;
+        let output = entry
+            .output_value
+            .iter()
+            .map(|&pattern| display.decode(&solution.mapping, pattern))
+            .collect::<Option<String>>()
+            .ok_or(format!("output doesn't match a glyph in {:?}", entry))?;
+        println!("mapped output = {:?}", output);
+        total = total.zip(output.parse::<i64>().ok()).map(|(a, b)| a + b);
+    }
+    match total {
+        Some(total) => println!("total = {}", total),
+        None => println!("not every output is a number, so there's no total"),
+    }
+    println!(
+        "{} of {} entries needed a guess",
+        guessed,
+        all_entries.len()
+    );
 
     Ok(())
 }
 
-/// apply the provided candidate mapping to each of the provided patterns
-/// returns the digits that the patterns map to, if any
-fn check_mapping(
-    patterns: &[String],
-    segments_to_digits: &BTreeMap<BTreeSet<char>, u8>,
-    candidate_mapping: &BTreeMap<char, char>,
-) -> Option<Vec<u8>> {
-    patterns
-        .iter()
-        .map(|pattern| {
-            let mapped_pattern = pattern
-                .chars()
-                .map(|ch| candidate_mapping[&ch])
-                .collect::<BTreeSet<char>>();
-            segments_to_digits.get(&mapped_pattern).map(|&digit| digit)
-        })
-        .collect()
+/// The segments a display lights for each glyph. Segments are numbered by the
+/// position of their letter among all the display's letters in sorted order, and a
+/// set of segments, or of the wires driving them, is a bit mask with bit `n` for
+/// segment `n`. Wires are named with the same letters as segments.
+struct Display {
+    letters: Vec<char>,
+    segments: usize,
+    glyphs: Vec<(String, u32)>,
+    glyph_for_segments: HashMap<u32, usize>,
+    /// for each number of glyphs a segment is lit in, the segments lit that often
+    segments_by_frequency: HashMap<usize, u32>,
+}
+
+/// a wiring that maps every signal pattern to a glyph, as the segment each wire
+/// drives, and whether it took a guess to find
+struct Solution {
+    mapping: Vec<usize>,
+    guessed: bool,
 }
 
-/// recursively explore a set of known-possible mappings
-/// returns a mapping that converts each of the patterns to a valid digit
-fn check_all_possible_mappings(
-    segments_to_digits: &BTreeMap<BTreeSet<char>, u8>,
-    patterns: &[String],
-    possible_mappings: &[(char, BTreeSet<char>)],
-    candidate_mapping: BTreeMap<char, char>,
-) -> Option<BTreeMap<char, char>> {
-    if !possible_mappings.is_empty() {
-        // recursively explore the possible mappings until we have built a complete
-        // candidate mapping
-        let (from, to_set) = &possible_mappings[0];
-        for to in to_set {
-            if candidate_mapping.values().any(|v| v == to) {
+impl Display {
+    /// Reads a display from its definition: a line per glyph with the glyph, then
+    /// whitespace, then the letters of the segments it lights. Blank lines and lines
+    /// starting with `#` are skipped.
+    fn parse(definition: &str) -> Result<Self> {
+        let mut shapes = vec![];
+        for line in definition.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
                 continue;
             }
-            let mut the_choices = candidate_mapping.clone();
-            the_choices.insert(*from, *to);
-            if let Some(mapping) = check_all_possible_mappings(
-                segments_to_digits,
-                patterns,
-                &possible_mappings[1..],
-                the_choices,
-            ) {
-                return Some(mapping);
+            let (glyph, lit) = line
+                .split_once(char::is_whitespace)
+                .ok_or(format!("no segments for glyph in {:?}", line))?;
+            let lit = lit.trim_start();
+            if let Some(ch) = lit.chars().find(|&ch| ch.is_whitespace() || ch == '|') {
+                Err(format!(
+                    "segment {:?} can't be told apart in {:?}",
+                    ch, line
+                ))?
+            }
+            shapes.push((glyph.to_string(), lit.chars().collect::<BTreeSet<char>>()));
+        }
+        let letters = shapes
+            .iter()
+            .flat_map(|(_, lit)| lit.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if letters.is_empty() || letters.len() > 32 {
+            Err(format!(
+                "a display needs 1 to 32 segments, not {}",
+                letters.len()
+            ))?
+        }
+        let segments = letters.len();
+        let glyphs = shapes
+            .into_iter()
+            .map(|(glyph, lit)| {
+                let mask = lit
+                    .iter()
+                    .map(|ch| 1 << letters.binary_search(ch).unwrap())
+                    .sum();
+                (glyph, mask)
+            })
+            .collect::<Vec<(String, u32)>>();
+        let mut glyph_for_segments = HashMap::new();
+        for (index, (glyph, lit)) in glyphs.iter().enumerate() {
+            if let Some(other) = glyph_for_segments.insert(*lit, index) {
+                Err(format!(
+                    "glyphs {:?} and {:?} light the same segments",
+                    glyphs[other].0, glyph
+                ))?
             }
         }
-        return None;
-    } else {
-        // we have a complete candidate, verify whether it works for all the patterns provided
-        assert_eq!(possible_mappings.len(), 0);
-        if let Some(_numbers) = check_mapping(patterns, segments_to_digits, &candidate_mapping) {
-            return Some(candidate_mapping);
+        let mut segments_by_frequency = HashMap::new();
+        for segment in 0..segments {
+            let frequency = glyphs
+                .iter()
+                .filter(|(_, lit)| lit & 1 << segment != 0)
+                .count();
+            *segments_by_frequency.entry(frequency).or_insert(0) |= 1 << segment;
         }
-        return None;
+        Ok(Display {
+            letters,
+            segments,
+            glyphs,
+            glyph_for_segments,
+            segments_by_frequency,
+        })
     }
-}
 
-fn solve_entry(
-    entry: &Entry,
-    digits_to_segments: &BTreeMap<u8, BTreeSet<char>>,
-    segments_to_digits: &BTreeMap<BTreeSet<char>, u8>,
-) -> i64 {
-    // all possible candidate mappings, where a pattern character is the key, and
-    // the set of segments it might map to is the value
-    let mut possible_mappings: BTreeMap<char, BTreeSet<char>> = ('a'..='g')
-        .map(|from| (from, BTreeSet::from_iter('a'..='g')))
-        .collect();
-    // remove impossible mappings by inferring digits based on length
-    for pattern in &entry.signal_patterns {
-        let digits_with_same_length: Vec<_> = digits_to_segments
-            .iter()
-            .filter(|(_, segments)| segments.len() == pattern.len())
-            .map(|(digit, _)| digit)
-            .collect();
-        let potential_digit_mappings = digits_with_same_length
-            .iter()
-            .map(|digit| digits_to_segments[digit].clone())
-            .reduce(|a: BTreeSet<char>, b| a.union(&b).map(|&c| c).collect())
-            .unwrap();
-        pattern.chars().for_each(|pattern_character| {
-            possible_mappings
-                .get_mut(&pattern_character)
-                .unwrap()
-                .retain(|ch| potential_digit_mappings.contains(ch));
-        });
+    /// the wires in a pattern written with the display's letters
+    fn parse_pattern(&self, pattern: &str) -> Result<u32> {
+        pattern
+            .chars()
+            .try_fold(0, |wires, ch| match self.letters.binary_search(&ch) {
+                Ok(wire) => Ok(wires | 1 << wire),
+                Err(_) => Err(format!("unexpected wire {:?} in {:?}", ch, pattern).into()),
+            })
     }
-    // for efficiency, we will consider mappings with fewer possibilities first
-    let mut possible_mappings: Vec<_> = possible_mappings.into_iter().collect();
-    possible_mappings.sort_by_key(|(_, to_set)| to_set.len());
-    if let Some(mapping) = check_all_possible_mappings(
-        &segments_to_digits,
-        &entry.signal_patterns,
-        &possible_mappings,
-        BTreeMap::new(),
-    ) {
-        let output = check_mapping(&entry.output_value, &segments_to_digits, &mapping)
-            .unwrap()
-            .into_iter()
-            .fold(0, |acc, d| acc * 10 + (d as i64));
 
-        println!("mapped output = {:?}", output);
-        return output;
+    /// the glyph a pattern of wires shows under a wiring
+    fn decode(&self, mapping: &[usize], pattern: u32) -> Option<&str> {
+        let lit = (0..mapping.len())
+            .filter(|&wire| pattern & 1 << wire != 0)
+            .map(|wire| 1 << mapping[wire])
+            .sum::<u32>();
+        self.glyph_for_segments
+            .get(&lit)
+            .map(|&index| self.glyphs[index].0.as_str())
+    }
+
+    /// Finds the wiring from the signal patterns. Each wire starts out able to drive
+    /// any segment. When every glyph is shown once, a wire must drive a segment that
+    /// is lit in as many glyphs as the wire is used in patterns. The rest is narrowed
+    /// by propagating constraints, only guessing if they run out before the wiring
+    /// is known.
+    fn solve(&self, patterns: &[u32]) -> Option<Solution> {
+        let all = u32::MAX >> (32 - self.segments);
+        let mut candidates = vec![all; self.segments];
+        let distinct = patterns.iter().collect::<BTreeSet<_>>().len();
+        if distinct == self.glyphs.len() && patterns.len() == distinct {
+            for (wire, candidate) in candidates.iter_mut().enumerate() {
+                let frequency = patterns
+                    .iter()
+                    .filter(|&&pattern| pattern & 1 << wire != 0)
+                    .count();
+                *candidate &= self.segments_by_frequency.get(&frequency).unwrap_or(&0);
+            }
+        }
+        self.search(patterns, candidates, false)
+    }
+
+    fn search(
+        &self,
+        patterns: &[u32],
+        mut candidates: Vec<u32>,
+        guessed: bool,
+    ) -> Option<Solution> {
+        if !self.propagate(patterns, &mut candidates) {
+            return None;
+        }
+        let undecided = (0..self.segments)
+            .filter(|&wire| candidates[wire].count_ones() > 1)
+            .min_by_key(|&wire| candidates[wire].count_ones());
+        let Some(wire) = undecided else {
+            let mapping = candidates
+                .iter()
+                .map(|candidate| candidate.trailing_zeros() as usize)
+                .collect::<Vec<_>>();
+            let solution = Solution { mapping, guessed };
+            return patterns
+                .iter()
+                .all(|&pattern| self.decode(&solution.mapping, pattern).is_some())
+                .then_some(solution);
+        };
+        (0..self.segments)
+            .filter(|&segment| candidates[wire] & 1 << segment != 0)
+            .find_map(|segment| {
+                let mut guess = candidates.clone();
+                guess[wire] = 1 << segment;
+                self.search(patterns, guess, true)
+            })
+    }
+
+    /// Narrows the segments each wire could drive until nothing changes, returning
+    /// false if some wire is left with none. Each pattern could only be a glyph with
+    /// as many segments, whose segments its wires could cover between them. The
+    /// pattern's wires can then only drive segments of one of those glyphs, and the
+    /// other wires can't drive a segment lit in all of them. A wire with only one
+    /// segment left is the only one to drive it, and a segment only one wire could
+    /// drive is driven by that wire.
+    fn propagate(&self, patterns: &[u32], candidates: &mut [u32]) -> bool {
+        let wires = 0..self.segments;
+        loop {
+            let before = candidates.to_vec();
+            for &pattern in patterns {
+                let (mut union, mut intersection) = (0, u32::MAX);
+                let in_pattern = |wire: &usize| pattern & 1 << wire != 0;
+                let reach = wires
+                    .clone()
+                    .filter(in_pattern)
+                    .fold(0, |reach, wire| reach | candidates[wire]);
+                for &(_, lit) in &self.glyphs {
+                    let possible = lit.count_ones() == pattern.count_ones()
+                        && reach & lit == lit
+                        && wires.clone().all(|wire| {
+                            let allowed = if in_pattern(&wire) { lit } else { !lit };
+                            candidates[wire] & allowed != 0
+                        });
+                    if possible {
+                        union |= lit;
+                        intersection &= lit;
+                    }
+                }
+                if union == 0 {
+                    return false;
+                }
+                for wire in wires.clone() {
+                    candidates[wire] &= if in_pattern(&wire) {
+                        union
+                    } else {
+                        !intersection
+                    };
+                }
+            }
+            for wire in wires.clone() {
+                if candidates[wire].count_ones() == 1 {
+                    for other in wires.clone().filter(|&other| other != wire) {
+                        candidates[other] &= !candidates[wire];
+                    }
+                }
+            }
+            for segment in wires.clone() {
+                let mut drivers = wires
+                    .clone()
+                    .filter(|&wire| candidates[wire] & 1 << segment != 0);
+                if let (Some(wire), None) = (drivers.next(), drivers.next()) {
+                    candidates[wire] = 1 << segment;
+                }
+            }
+            if candidates.contains(&0) {
+                return false;
+            }
+            if candidates == before.as_slice() {
+                return true;
+            }
+        }
     }
-    panic!("didn't manage to solve for entry {:?}", &entry);
 }
 
 #[derive(Clone, Debug)]
 struct Entry {
-    signal_patterns: Vec<String>,
-    output_value: Vec<String>,
+    signal_patterns: Vec<u32>,
+    output_value: Vec<u32>,
 }
 
-fn read_input() -> Result<Vec<Entry>> {
+fn read_input(display: &Display) -> Result<Vec<Entry>> {
     let mut entries = vec![];
     let stdin = io::stdin();
     for line in stdin.lock().lines() {
@@ -155,13 +312,119 @@ fn read_input() -> Result<Vec<Entry>> {
         entries.push(Entry {
             signal_patterns: first_part
                 .split_ascii_whitespace()
-                .map(String::from)
-                .collect(),
+                .map(|pattern| display.parse_pattern(pattern))
+                .collect::<Result<_>>()?,
             output_value: second_part
                 .split_ascii_whitespace()
-                .map(String::from)
-                .collect(),
+                .map(|pattern| display.parse_pattern(pattern))
+                .collect::<Result<_>>()?,
         });
     }
     Ok(entries)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Display, SEVEN_SEGMENT};
+
+    fn patterns(display: &Display, patterns: &str) -> Vec<u32> {
+        patterns
+            .split_whitespace()
+            .map(|pattern| display.parse_pattern(pattern).unwrap())
+            .collect()
+    }
+
+    fn decode_all(display: &Display, mapping: &[usize], patterns: &[u32]) -> Vec<String> {
+        patterns
+            .iter()
+            .map(|&pattern| display.decode(mapping, pattern).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn propagation_alone() {
+        let display = Display::parse(SEVEN_SEGMENT).unwrap();
+        let signals = patterns(
+            &display,
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab",
+        );
+        let solution = display.solve(&signals).unwrap();
+        assert!(!solution.guessed);
+        let output = patterns(&display, "cdfeb fcadb cdfeb cdbaf");
+        assert_eq!(
+            decode_all(&display, &solution.mapping, &output),
+            vec!["5", "3", "5", "3"]
+        );
+    }
+
+    #[test]
+    fn guesses_when_underdetermined() {
+        let display = Display::parse(SEVEN_SEGMENT).unwrap();
+        let signals = patterns(&display, "ab dab");
+        let solution = display.solve(&signals).unwrap();
+        assert!(solution.guessed);
+        assert_eq!(
+            decode_all(&display, &solution.mapping, &signals),
+            vec!["1", "7"]
+        );
+        assert!(display.solve(&patterns(&display, "ab abc abde")).is_some());
+        assert!(display
+            .solve(&patterns(&display, "abcdefg abcdef abcde abcd abc ab a"))
+            .is_none());
+    }
+
+    #[test]
+    fn alphanumeric_display() {
+        let definition = "\
+# a 14-segment display, with the middle bar split into g and h
+0 abcdefjm
+1 bcj
+2 abdegh
+3 abcdh
+4 bcfgh
+5 adfgkl
+6 acdefgh
+7 abc
+8 abcdefgh
+9 abcdfgh
+A abcefgh
+B abcdhin
+C adef
+D abcdin
+E adefg
+F aefg
+";
+        let display = Display::parse(definition).unwrap();
+        assert_eq!(display.segments, 14);
+        let glyphs = definition
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split_once(' ').unwrap())
+            .collect::<Vec<_>>();
+        let scrambled = glyphs
+            .iter()
+            .rev()
+            .map(|(_, lit)| {
+                lit.chars()
+                    .map(|ch| display.letters[(5 * (ch as usize - 'a' as usize) + 3) % 14])
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let signals = patterns(&display, &scrambled);
+        let solution = display.solve(&signals).unwrap();
+        let expected = glyphs.iter().rev().map(|(glyph, _)| glyph.to_string());
+        assert_eq!(
+            decode_all(&display, &solution.mapping, &signals),
+            expected.collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bad_definitions() {
+        assert!(Display::parse("").is_err());
+        assert!(Display::parse("1 cf\n7 cf").is_err());
+        assert!(Display::parse("1").is_err());
+        assert!(Display::parse("1 c|f").is_err());
+    }
+}